@@ -0,0 +1,708 @@
+// Local stand-in for the Slack Web API, serving the fixtures in fixtures/
+//
+// Run with `cargo run --example mock_slack [port]` and start the client with
+// `SLACK_API_URL=http://127.0.0.1:8080/api cargo run`
+//
+// A websocket on the next port replays the events in fixtures/realtime_events.jsonl
+// to every client that connects through apps.connections.open or rtm.connect. Tokens
+// ending in "reconnect" get a disconnect event after the replay
+//
+// /oauth/v2/authorize approves every request right away and redirects back with a code
+// that oauth.v2.access exchanges for a new token, so `slacker-tui login` can be tried with
+// `"authorize_url": "http://127.0.0.1:8080/oauth/v2/authorize"`
+//
+// Tokens ending in "ratelimited" get 429 with Retry-After for every method, to try out
+// the rate limiter
+//
+// The tests of the client start it with `start(0, Duration::ZERO)` on free ports
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tungstenite::handshake::server::{Callback, ErrorResponse, Request as WsRequest, Response as WsResponse};
+use tungstenite::Message as WsMessage;
+
+const DEFAULT_PORT: u16 = 8080;
+
+const CHANNELS: &str = include_str!("../fixtures/conversations.list.json");
+const USERS: &str = include_str!("../fixtures/users.list.json");
+const HISTORY: &str = include_str!("../fixtures/conversations.history.json");
+const REPLIES: &str = include_str!("../fixtures/conversations.replies.json");
+const EMOJI: &str = include_str!("../fixtures/emoji.list.json");
+const TEAMS: &str = include_str!("../fixtures/team.info.json");
+const REALTIME_EVENTS: &str = include_str!("../fixtures/realtime_events.jsonl");
+
+const REPLAY_DELAY: Duration = Duration::from_secs(2);
+
+// Retry-After of the 429 responses to rate limited tokens
+const RETRY_AFTER_SECONDS: u64 = 1;
+
+// Messages are posted by the first user in the fixtures
+const MOCK_USER_ID: &str = "U012AB3CDE";
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+struct MockSlack {
+    websocket_url: String,
+    channels: Vec<Value>,
+    users: Vec<Value>,
+    // Messages and thread replies per channel id, newest first
+    history: HashMap<String, Vec<Value>>,
+    teams: Vec<Value>,
+    // Every new token belongs to the next team, so several workspaces can be tried
+    team_by_token: HashMap<String, usize>,
+    // Codes handed out by /oauth/v2/authorize that were not exchanged yet
+    oauth_codes: HashSet<String>,
+    issued_tokens: usize,
+}
+
+impl MockSlack {
+    fn new(websocket_url: String) -> Self {
+        let channels: Value = serde_json::from_str(CHANNELS).expect("Parse channels fixture expect");
+        let users: Value = serde_json::from_str(USERS).expect("Parse users fixture expect");
+        let history: Value = serde_json::from_str(HISTORY).expect("Parse history fixture expect");
+        let replies: Value = serde_json::from_str(REPLIES).expect("Parse replies fixture expect");
+
+        let channels = channels["channels"].as_array().cloned().unwrap_or_default();
+        let mut messages = history["messages"].as_array().cloned().unwrap_or_default();
+        messages.extend(replies["messages"].as_array().cloned().unwrap_or_default());
+        messages.sort_by(|a, b| ts(b).total_cmp(&ts(a)));
+        // The parent of the thread is in both fixtures
+        messages.dedup_by(|a, b| a["ts"] == b["ts"]);
+        let history = channels.iter()
+            .filter_map(|channel| channel["id"].as_str())
+            .map(|id| (id.to_string(), messages.clone()))
+            .collect();
+
+        MockSlack {
+            websocket_url,
+            channels,
+            users: users["members"].as_array().cloned().unwrap_or_default(),
+            history,
+            teams: serde_json::from_str::<Value>(TEAMS).expect("Parse teams fixture expect")["teams"]
+                .as_array().cloned().unwrap_or_default(),
+            team_by_token: HashMap::new(),
+            oauth_codes: HashSet::new(),
+            issued_tokens: 0,
+        }
+    }
+
+    fn handle(&mut self, request: &Request) -> Value {
+        let params = request.params();
+        let api_method = request.path.trim_start_matches("/api/");
+        // The OAuth exchange is the only method that is called without a token
+        if api_method == "oauth.v2.access" {
+            return self.exchange_code(&params);
+        }
+        if !request.headers.contains_key("authorization") {
+            return error("not_authed");
+        }
+
+        match api_method {
+            "conversations.list" => {
+                // Like Slack only public channels are listed without types
+                let types = match param(&params, "types") {
+                    "" => "public_channel",
+                    types => types,
+                };
+                let channels: Vec<Value> = self.channels.iter()
+                    .filter(|channel| types.split(',').any(|kind| kind == channel_type(channel)))
+                    .cloned()
+                    .collect();
+                paginate(&channels, "channels", &params)
+            }
+            "conversations.info" => {
+                let channel_id = param(&params, "channel");
+                let Some(mut channel) = self.channels.iter().find(|c| c["id"] == channel_id).cloned() else {
+                    return error("channel_not_found");
+                };
+                let messages = self.history.get(channel_id).cloned().unwrap_or_default();
+                // Everything is read unless the fixture says otherwise
+                if channel["last_read"].is_null() {
+                    channel["last_read"] = messages.first().map(|m| m["ts"].clone()).unwrap_or(json!("0000000000.000000"));
+                }
+                // Like Slack, unread messages are only counted outside of public channels
+                if channel_type(&channel) != "public_channel" {
+                    let last_read: f64 = channel["last_read"].as_str().unwrap_or_default().parse().unwrap_or(0.0);
+                    let unread = messages.iter().filter(|m| ts(m) > last_read && !is_reply(m)).count();
+                    channel["unread_count_display"] = json!(unread);
+                }
+                json!({ "ok": true, "channel": channel })
+            }
+            "conversations.mark" => {
+                let channel_id = param(&params, "channel");
+                match self.channels.iter_mut().find(|c| c["id"] == channel_id) {
+                    Some(channel) => {
+                        channel["last_read"] = json!(param(&params, "ts"));
+                        json!({ "ok": true })
+                    }
+                    None => error("channel_not_found"),
+                }
+            }
+            "team.info" => match self.get_team(&get_token(request)) {
+                Some(team) => json!({ "ok": true, "team": team }),
+                None => error("team_not_found"),
+            },
+            "auth.test" => {
+                // Tokens ending in "invalid" are rejected to try out error handling
+                let token = get_token(request);
+                if token.ends_with("invalid") {
+                    return error("invalid_auth");
+                }
+                match self.get_team(&token) {
+                    Some(team) => json!({
+                        "ok": true,
+                        "url": format!("https://{}.slack.com/", team["domain"].as_str().unwrap_or_default()),
+                        "team": team["name"],
+                        "user": "spengler",
+                        "team_id": team["id"],
+                        "user_id": MOCK_USER_ID,
+                    }),
+                    None => error("team_not_found"),
+                }
+            }
+            "users.list" => paginate(&self.users, "members", &params),
+            "users.info" => {
+                match self.users.iter().find(|user| user["id"] == param(&params, "user")) {
+                    Some(user) => json!({ "ok": true, "user": user }),
+                    None => error("user_not_found"),
+                }
+            }
+            "conversations.history" => {
+                let oldest: f64 = param(&params, "oldest").parse().unwrap_or(0.0);
+                match self.history.get(param(&params, "channel")) {
+                    Some(messages) => {
+                        // Like Slack, replies are only in the history when sent to the channel
+                        let messages: Vec<Value> = messages.iter()
+                            .filter(|m| ts(m) > oldest)
+                            .filter(|m| !is_reply(m) || m["subtype"] == "thread_broadcast")
+                            .cloned()
+                            .collect();
+                        paginate(&messages, "messages", &params)
+                    }
+                    None => error("channel_not_found"),
+                }
+            }
+            "conversations.replies" => {
+                let thread_ts = param(&params, "ts");
+                match self.history.get(param(&params, "channel")) {
+                    Some(messages) => {
+                        // The parent first, then the replies oldest first
+                        let thread: Vec<Value> = messages.iter().rev()
+                            .filter(|m| m["ts"] == thread_ts || m["thread_ts"] == thread_ts)
+                            .cloned()
+                            .collect();
+                        if thread.is_empty() {
+                            return error("thread_not_found");
+                        }
+                        paginate(&thread, "messages", &params)
+                    }
+                    None => error("channel_not_found"),
+                }
+            }
+            "chat.postMessage" => {
+                let channel = param(&params, "channel").to_string();
+                // The client may send the channel name instead of the id
+                let channel_id = self.channels.iter()
+                    .find(|c| c["id"] == channel.as_str() || c["name"] == channel.as_str())
+                    .and_then(|c| c["id"].as_str())
+                    .map(|id| id.to_string())
+                    .or_else(|| self.history.contains_key(&channel).then_some(channel));
+
+                let Some(channel_id) = channel_id else {
+                    return error("channel_not_found");
+                };
+                let mut message = json!({
+                    "type": "message",
+                    "user": MOCK_USER_ID,
+                    "text": param(&params, "text"),
+                    "ts": timestamp(),
+                });
+
+                let messages = self.history.entry(channel_id.clone()).or_default();
+                let thread_ts = param(&params, "thread_ts");
+                if !thread_ts.is_empty() {
+                    let Some(parent) = messages.iter_mut().find(|m| m["ts"] == thread_ts) else {
+                        return error("thread_not_found");
+                    };
+                    add_reply(parent, &message);
+                    message["thread_ts"] = json!(thread_ts);
+                    if param(&params, "reply_broadcast") == "true" {
+                        message["subtype"] = json!("thread_broadcast");
+                    }
+                }
+                messages.insert(0, message.clone());
+                json!({ "ok": true, "channel": channel_id, "ts": message["ts"], "message": message })
+            }
+            "chat.update" | "chat.delete" => {
+                let channel_id = param(&params, "channel");
+                let ts = param(&params, "ts");
+                let Some(messages) = self.history.get_mut(channel_id) else {
+                    return error("channel_not_found");
+                };
+                let Some(index) = messages.iter().position(|m| m["ts"] == ts) else {
+                    return error("message_not_found");
+                };
+
+                // Only messages posted by the mock user can be changed
+                let is_update = api_method == "chat.update";
+                if messages[index]["user"] != MOCK_USER_ID {
+                    return error(if is_update { "cant_update_message" } else { "cant_delete_message" });
+                }
+                if is_update {
+                    let message = &mut messages[index];
+                    message["text"] = json!(param(&params, "text"));
+                    message["edited"] = json!({ "user": MOCK_USER_ID, "ts": timestamp() });
+                    json!({ "ok": true, "channel": channel_id, "ts": ts, "text": message["text"] })
+                }
+                else {
+                    messages.remove(index);
+                    json!({ "ok": true, "channel": channel_id, "ts": ts })
+                }
+            }
+            "reactions.add" | "reactions.remove" | "reactions.get" => {
+                let channel_id = param(&params, "channel");
+                let ts = param(&params, "timestamp");
+                let Some(message) = self.history.get_mut(channel_id)
+                    .and_then(|messages| messages.iter_mut().find(|m| m["ts"] == ts)) else {
+                    return error("message_not_found");
+                };
+
+                let result = match api_method {
+                    "reactions.add" => add_reaction(message, param(&params, "name")),
+                    "reactions.remove" => remove_reaction(message, param(&params, "name")),
+                    _ => Ok(()),
+                };
+                match result {
+                    Ok(()) => json!({
+                        "ok": true,
+                        "type": "message",
+                        "channel": channel_id,
+                        "message": message,
+                    }),
+                    Err(message) => error(message),
+                }
+            }
+            "emoji.list" => serde_json::from_str(EMOJI).expect("Parse emoji fixture expect"),
+            "conversations.open" => {
+                let user_id = param(&params, "users");
+                if !self.users.iter().any(|user| user["id"] == user_id) {
+                    return error("user_not_found");
+                }
+
+                // IM ids mirror the user id, U012AB3CDE -> D012AB3CDE
+                let channel_id = format!("D{}", &user_id[1..]);
+                self.history.entry(channel_id.clone()).or_default();
+                json!({ "ok": true, "channel": { "id": channel_id } })
+            }
+            "apps.connections.open" | "rtm.connect" => {
+                // The token tells the websocket whom the acknowledgements are from
+                json!({ "ok": true, "url": format!("{}?token={}", self.websocket_url, get_token(request)) })
+            }
+            _ => error("unknown_method"),
+        }
+    }
+
+    // Every new token gets the next team so several workspaces can use one mock server
+    fn get_team(&mut self, token: &str) -> Option<&Value> {
+        let next_team = self.team_by_token.len();
+        let index = *self.team_by_token.entry(token.to_string()).or_insert(next_team);
+        self.teams.get(index % self.teams.len().max(1))
+    }
+
+    // Where /oauth/v2/authorize sends the browser, the user always approves
+    fn authorize(&mut self, request: &Request) -> Option<String> {
+        let redirect_uri = request.query.get("redirect_uri")?;
+        let state = request.query.get("state").cloned().unwrap_or_default();
+        if request.query.get("client_id").is_none_or(|client_id| client_id.is_empty()) {
+            return Some(format!("{}?error=invalid_client_id&state={}", redirect_uri, state));
+        }
+        let code = format!("mock-code-{}", self.oauth_codes.len() + self.issued_tokens);
+        self.oauth_codes.insert(code.clone());
+        Some(format!("{}?code={}&state={}", redirect_uri, code, state))
+    }
+
+    fn exchange_code(&mut self, params: &HashMap<String, String>) -> Value {
+        if param(params, "client_id").is_empty() || param(params, "client_secret").is_empty() {
+            return error("invalid_client_id");
+        }
+        if !self.oauth_codes.remove(param(params, "code")) {
+            return error("invalid_code");
+        }
+
+        // Every login is a new token, so it gets the next team
+        self.issued_tokens += 1;
+        let token = format!("xoxp-mock-{}", self.issued_tokens);
+        let Some(team) = self.get_team(&token).cloned() else {
+            return error("team_not_found");
+        };
+        json!({
+            "ok": true,
+            "app_id": "A0MOCK001",
+            "authed_user": {
+                "id": MOCK_USER_ID,
+                "access_token": token,
+                "token_type": "user",
+            },
+            "team": { "id": team["id"], "name": team["name"] },
+            "is_enterprise_install": false,
+        })
+    }
+}
+
+// The token in the Authorization header, "Bearer xoxp-..."
+fn get_token(request: &Request) -> String {
+    let authorization = request.headers.get("authorization").cloned().unwrap_or_default();
+    authorization.trim_start_matches("Bearer ").to_string()
+}
+
+impl Request {
+    fn read(stream: &mut TcpStream) -> Option<Request> {
+        let mut reader = BufReader::new(stream);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).ok()?;
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next()?.to_string();
+        let target = parts.next()?;
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).ok()?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+
+        let length = headers.get("content-length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).ok()?;
+
+        Some(Request {
+            method,
+            path: path.to_string(),
+            query: parse_query(query),
+            headers,
+            body,
+        })
+    }
+
+    // Query string parameters merged with form or JSON body parameters
+    fn params(&self) -> HashMap<String, String> {
+        let mut params = self.query.clone();
+        if self.method == "POST" {
+            let content_type = self.headers.get("content-type").cloned().unwrap_or_default();
+            if content_type.starts_with("application/json") {
+                if let Ok(Value::Object(body)) = serde_json::from_slice::<Value>(&self.body) {
+                    for (key, value) in body {
+                        let value = match value {
+                            Value::String(value) => value,
+                            value => value.to_string(),
+                        };
+                        params.insert(key, value);
+                    }
+                }
+            }
+            else {
+                params.extend(parse_query(&String::from_utf8_lossy(&self.body)));
+            }
+        }
+        params
+    }
+}
+
+fn param<'a>(params: &'a HashMap<String, String>, name: &str) -> &'a str {
+    params.get(name).map(|value| value.as_str()).unwrap_or_default()
+}
+
+fn error(error: &str) -> Value {
+    json!({ "ok": false, "error": error })
+}
+
+// Serve `items` in pages, the cursor is the offset of the next page
+fn paginate(items: &[Value], field: &str, params: &HashMap<String, String>) -> Value {
+    let limit = param(params, "limit").parse().unwrap_or(100).max(1);
+    let offset: usize = param(params, "cursor").parse().unwrap_or(0);
+
+    let page: Vec<Value> = items.iter().skip(offset).take(limit).cloned().collect();
+    let next_cursor = if offset + limit < items.len() {
+        (offset + limit).to_string()
+    } else {
+        String::new()
+    };
+
+    json!({
+        "ok": true,
+        field: page,
+        "has_more": !next_cursor.is_empty(),
+        "response_metadata": { "next_cursor": next_cursor },
+    })
+}
+
+fn channel_type(channel: &Value) -> &'static str {
+    let is = |field: &str| channel[field].as_bool().unwrap_or(false);
+    if is("is_im") {
+        "im"
+    } else if is("is_mpim") {
+        "mpim"
+    } else if is("is_private") {
+        "private_channel"
+    } else {
+        "public_channel"
+    }
+}
+
+fn ts(message: &Value) -> f64 {
+    message["ts"].as_str().and_then(|ts| ts.parse().ok()).unwrap_or(0.0)
+}
+
+fn is_reply(message: &Value) -> bool {
+    message["thread_ts"].as_str().is_some_and(|thread_ts| message["ts"] != thread_ts)
+}
+
+// Update the thread fields of a parent message for a new reply
+fn add_reply(parent: &mut Value, reply: &Value) {
+    let count = parent["reply_count"].as_u64().unwrap_or(0);
+    parent["thread_ts"] = parent["ts"].clone();
+    parent["reply_count"] = json!(count + 1);
+    parent["latest_reply"] = reply["ts"].clone();
+
+    let mut users = parent["reply_users"].as_array().cloned().unwrap_or_default();
+    if !users.contains(&reply["user"]) {
+        users.push(reply["user"].clone());
+    }
+    parent["reply_users"] = json!(users);
+}
+
+fn add_reaction(message: &mut Value, name: &str) -> Result<(), &'static str> {
+    let mut reactions = message["reactions"].as_array().cloned().unwrap_or_default();
+    match reactions.iter_mut().find(|reaction| reaction["name"] == name) {
+        Some(reaction) => {
+            let mut users = reaction["users"].as_array().cloned().unwrap_or_default();
+            if users.contains(&json!(MOCK_USER_ID)) {
+                return Err("already_reacted");
+            }
+            users.push(json!(MOCK_USER_ID));
+            reaction["count"] = json!(users.len());
+            reaction["users"] = json!(users);
+        }
+        None => reactions.push(json!({ "name": name, "count": 1, "users": [MOCK_USER_ID] })),
+    }
+    message["reactions"] = json!(reactions);
+    Ok(())
+}
+
+fn remove_reaction(message: &mut Value, name: &str) -> Result<(), &'static str> {
+    let mut reactions = message["reactions"].as_array().cloned().unwrap_or_default();
+    let reaction = reactions.iter_mut()
+        .find(|reaction| reaction["name"] == name)
+        .ok_or("no_reaction")?;
+    let mut users = reaction["users"].as_array().cloned().unwrap_or_default();
+    if !users.contains(&json!(MOCK_USER_ID)) {
+        return Err("no_reaction");
+    }
+    users.retain(|user| user != MOCK_USER_ID);
+    reaction["count"] = json!(users.len());
+    reaction["users"] = json!(users);
+
+    reactions.retain(|reaction| reaction["count"] != 0);
+    message["reactions"] = json!(reactions);
+    Ok(())
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+// Decode a percent encoded query string value
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}.{:06}", now.as_secs(), now.subsec_micros())
+}
+
+fn respond(stream: &mut TcpStream, body: &Value) -> std::io::Result<()> {
+    let body = body.to_string();
+    write!(stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(), body)
+}
+
+fn rate_limited(stream: &mut TcpStream) -> std::io::Result<()> {
+    write!(stream,
+        "HTTP/1.1 429 Too Many Requests\r\nRetry-After: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        RETRY_AFTER_SECONDS)
+}
+
+fn redirect(stream: &mut TcpStream, location: &str) -> std::io::Result<()> {
+    write!(stream,
+        "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        location)
+}
+
+// Reads the token from the url of the websocket handshake
+struct ReadToken<'a>(&'a mut String);
+
+impl Callback for ReadToken<'_> {
+    fn on_request(self, request: &WsRequest, response: WsResponse) -> Result<WsResponse, ErrorResponse> {
+        let query = parse_query(request.uri().query().unwrap_or_default());
+        *self.0 = query.get("token").cloned().unwrap_or_default();
+        Ok(response)
+    }
+}
+
+// Send hello and replay the recorded events, then keep the connection open and note the
+// envelopes that are acknowledged
+fn replay_events(stream: TcpStream, replay_delay: Duration, acks: Acks) -> Result<(), Box<dyn std::error::Error>> {
+    let mut token = String::new();
+    let mut socket = tungstenite::accept_hdr(stream, ReadToken(&mut token))
+        .map_err(|error| error.to_string())?;
+
+    socket.send(WsMessage::Text(json!({ "type": "hello" }).to_string()))?;
+    for event in REALTIME_EVENTS.lines().filter(|line| !line.trim().is_empty()) {
+        thread::sleep(replay_delay);
+        socket.send(WsMessage::Text(event.to_string()))?;
+        println!("WS {}", event.chars().take(60).collect::<String>());
+    }
+    if token.ends_with("reconnect") {
+        socket.send(WsMessage::Text(json!({ "type": "disconnect", "reason": "refresh_requested" }).to_string()))?;
+    }
+
+    loop {
+        match socket.read()? {
+            WsMessage::Text(text) => {
+                let ack: Value = serde_json::from_str(&text).unwrap_or_default();
+                if let Some(envelope_id) = ack["envelope_id"].as_str() {
+                    acks.lock().expect("Lock acks expect")
+                        .entry(token.clone())
+                        .or_default()
+                        .push(envelope_id.to_string());
+                }
+            }
+            WsMessage::Close(_) => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+// Acknowledged envelope ids by the token of the connection
+pub type Acks = Arc<Mutex<HashMap<String, Vec<String>>>>;
+
+// Where a running mock server can be reached
+pub struct MockServer {
+    pub api_url: String,
+    pub websocket_url: String,
+    pub acks: Acks,
+}
+
+// Serve the API on `port` and the websocket on the next port from background threads,
+// events are replayed `replay_delay` apart. With port 0 both get a free port
+pub fn start(port: u16, replay_delay: Duration) -> std::io::Result<MockServer> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let api_url = format!("http://{}/api", listener.local_addr()?);
+
+    let websocket_port = if port == 0 { 0 } else { port + 1 };
+    let websocket_listener = TcpListener::bind(("127.0.0.1", websocket_port))?;
+    let websocket_url = format!("ws://{}/", websocket_listener.local_addr()?);
+    let acks = Acks::default();
+    let websocket_acks = Arc::clone(&acks);
+    thread::spawn(move || {
+        for stream in websocket_listener.incoming().flatten() {
+            let acks = Arc::clone(&websocket_acks);
+            thread::spawn(move || {
+                if let Err(error) = replay_events(stream, replay_delay, acks) {
+                    println!("WS closed: {}", error);
+                }
+            });
+        }
+    });
+
+    let slack = Arc::new(Mutex::new(MockSlack::new(websocket_url.clone())));
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let slack = Arc::clone(&slack);
+            thread::spawn(move || {
+                if let Some(request) = Request::read(&mut stream) {
+                    if request.path == "/oauth/v2/authorize" {
+                        let location = slack.lock().expect("Lock mock state expect").authorize(&request);
+                        println!("{} {} -> {:?}", request.method, request.path, location);
+                        let _ = match location {
+                            Some(location) => redirect(&mut stream, &location),
+                            None => respond(&mut stream, &error("bad_redirect_uri")),
+                        };
+                        return;
+                    }
+                    if get_token(&request).ends_with("ratelimited") {
+                        println!("{} {} -> 429", request.method, request.path);
+                        let _ = rate_limited(&mut stream);
+                        return;
+                    }
+                    let response = slack.lock().expect("Lock mock state expect").handle(&request);
+                    println!("{} {} -> {}", request.method, request.path, response["ok"]);
+                    let _ = respond(&mut stream, &response);
+                }
+            });
+        }
+    });
+
+    Ok(MockServer { api_url, websocket_url, acks })
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let port = std::env::args().nth(1)
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+
+    let server = start(port, REPLAY_DELAY)?;
+    println!("Mock Slack API listening on {}", server.api_url);
+    println!("Mock Slack websocket listening on {}", server.websocket_url);
+    loop {
+        thread::park();
+    }
+}
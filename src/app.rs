@@ -1,21 +1,39 @@
 use crossterm::{
-    event::{self, Event as CEvent, KeyCode, KeyEvent, KeyModifiers},
-    terminal::{disable_raw_mode, enable_raw_mode},
-    cursor::{MoveTo}, ExecutableCommand,
+    event::KeyEvent,
+    terminal::disable_raw_mode,
+    cursor::MoveTo, ExecutableCommand,
 };
 // use rand::{distributions::Alphanumeric, prelude::*};
-use serde_json::Value;
+use std::collections::HashMap;
 use std::io;
 use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tui::{
-    backend::{CrosstermBackend, Backend},
+    backend::CrosstermBackend,
     Terminal,
 };
 
-use crate::{InputEvent, };
-use crate::input_reciever::{InputReciever};
-use crate::slack_interface::{user_interface, channel_interface, messages_interface};
-use crate::ui;
+use crate::InputEvent;
+use crate::input_reciever::InputReciever;
+use crate::keymap::{Context, Keymap};
+use crate::network::{ApiRequest, ApiResponse};
+use crate::slack_interface::{self, auth_interface::Identity, user_interface, channel_interface, messages_interface, SlackError};
+use crate::slack_interface::realtime_interface::RealtimeEvent;
+use crate::emoji::{self, EmojiPicker, PickerMode};
+use crate::config::Config;
+use crate::workspace::{Workspace, WorkspaceState};
+use crate::timestamp::TimeFormat;
+use crate::{mrkdwn, realtime, ui, util};
+
+// How long a typing indicator is shown after the last user_typing event
+const TYPING_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Conversations checked for unread messages each round, well under Slack's 50 requests per
+// minute for conversations.history
+const UNREAD_POLL_BATCH: usize = 20;
+
+// Number of messages PageUp and PageDown move the selection
+pub const MESSAGES_PAGE_SIZE: usize = 10;
 
 #[derive(Debug, PartialOrd, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum ActiveBlock {
@@ -27,6 +45,25 @@ pub enum ActiveBlock {
     Messages,
     Input,
     Search,
+    Thread,
+}
+
+// Position of the messages pane. Anchored to the message at the top of the view so new
+// and older messages do not move what is shown
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MessagesScroll {
+    Bottom,
+    // Lines into the message with `ts` at the top of the view
+    Anchor { ts: String, offset: usize },
+}
+
+// Unread messages in a conversation that is not being viewed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Unread {
+    pub count: u64,
+    pub mentions: u64,
+    // Only the newest page was counted, there are more unread messages
+    pub capped: bool,
 }
 
 // Convert MenuItem to usize, will be used to
@@ -42,76 +79,1285 @@ impl From<ActiveBlock> for usize {
             ActiveBlock::Teams => 5,
             ActiveBlock::Search => 6,
             ActiveBlock::Home => 7,
+            ActiveBlock::Thread => 8,
         }
     }
 }
 
-pub struct App<'a>{
-    pub config: Value,
+// The keymap context of the keys pressed while a block is active
+impl From<ActiveBlock> for Context {
+    fn from(item: ActiveBlock) -> Context {
+        match item {
+            ActiveBlock::Teams | ActiveBlock::Channels | ActiveBlock::Users => Context::Sidebar,
+            ActiveBlock::Messages | ActiveBlock::Thread => Context::Messages,
+            ActiveBlock::Input => Context::Input,
+            _ => Context::Global,
+        }
+    }
+}
+
+pub struct App{
     pub oauth_token: String,
+    pub network: mpsc::Sender<ApiRequest>,
+    // Worker for polling so it never delays requests made by the user
+    pub background_network: mpsc::Sender<ApiRequest>,
+    // Worker for polling the open conversation, so it does not wait behind the unread checks
+    pub poll_network: mpsc::Sender<ApiRequest>,
+    pub realtime_token: Option<String>,
+    // Where the workers of every workspace send their events
+    pub events: mpsc::Sender<InputEvent<KeyEvent>>,
+    // Number of requests in flight per block, used to show loading spinners
+    pub loading: HashMap<ActiveBlock, usize>,
+    pub spinner_index: usize,
+    pub realtime_connected: bool,
+    // Users typing per (conversation id, user id), with the time of the last event
+    pub typing_users: HashMap<(String, String), Instant>,
+    // Presence per user id, "active" or "away"
+    pub presence: HashMap<String, String>,
+    pub poll_interval: Duration,
+    pub unread_poll_interval: Duration,
+    pub last_poll: Instant,
+    pub last_unread_poll: Instant,
+    pub is_polling: bool,
+    // Unread checks and read states that have not been answered yet
+    pub pending_unread: usize,
+    // Where the next round of unread checks starts in the channel list
+    pub unread_poll_offset: usize,
+    // Timestamp of the newest message seen per conversation id
+    pub last_seen: HashMap<String, String>,
+    pub startup_ts: String,
+    pub unread: HashMap<String, Unread>,
     pub active_block: ActiveBlock,
     pub hovered_block: ActiveBlock,
-    pub team_list: Vec<&'a str>,
+    pub workspaces: Vec<Workspace>,
+    // Index of the workspace whose state is in App
+    pub active_workspace: usize,
+    // Set while an event of a workspace that is not shown is handled
+    pub is_background: bool,
     pub channel_list: Vec<channel_interface::Channel>,
     pub user_list: Vec<user_interface::User>,
+    pub user_directory: user_interface::UserDirectory,
     pub selected_team_index: Option<usize>,
     pub selected_channel_index: Option<usize>,
     pub selected_user_index: Option<usize>,
     pub messages_list: Vec<messages_interface::Message>,
+    pub messages_cursor: Option<String>,
+    // Timestamp of the selected message, the actions on messages apply to it
+    pub selected_message_ts: Option<String>,
+    pub messages_scroll: MessagesScroll,
+    // Messages that arrived below the view while scrolled up
+    pub new_messages_below: usize,
+    // Parent timestamp of the thread shown next to the messages
+    pub thread_ts: Option<String>,
+    // Newest first like messages_list, the parent message is last
+    pub thread_messages: Vec<messages_interface::Message>,
+    pub thread_scroll: u16,
+    // Send the input as a reply to the open thread, optionally also to the channel
+    pub reply_in_thread: bool,
+    pub reply_broadcast: bool,
+    pub emoji_picker: Option<EmojiPicker>,
+    // Timestamp of the message being edited in the input
+    pub editing_ts: Option<String>,
+    // Message waiting for the user to confirm its deletion, (conversation id, ts)
+    pub pending_delete: Option<(String, String)>,
+    // Custom workspace emoji, loaded when the picker is first opened
+    pub custom_emoji: Vec<String>,
+    pub is_emoji_requested: bool,
+    pub time_format: TimeFormat,
+    pub message_group_window: Duration,
+    pub keymap: Keymap,
     pub active_conversation_id: Option<String>,
     pub active_conversation_name: Option<String>,
+    // Who the token signs in as, from auth.test
+    pub identity: Option<Identity>,
+    // Why the token was rejected, shown instead of the conversation
+    pub auth_error: Option<String>,
     pub input: Vec<char>,
     pub input_idx: usize,
     pub input_cursor_position: u16,
     pub input_cursor_horizontal_offset: u16,
     pub input_cursor_vertical_offset: u16,
+    pub error_message: Option<String>,
 }
 
-impl<'a> App<'a> {
-    pub fn new() -> Self {
-        let config = crate::parse_config().expect("Parse config expect");
-        let oauth_token = &config["oauth_token"].as_str()
-            .expect("OAuth token is not a string").to_string();
+impl App {
+    pub fn new(config: Config, warnings: Vec<String>, events: mpsc::Sender<InputEvent<KeyEvent>>) -> Self {
+        slack_interface::set_api_url(&config.api_url);
+        let page_limit = config.page_limit;
+        let poll_interval = Duration::from_millis(config.poll_interval_ms);
+        let unread_poll_interval = Duration::from_millis(config.unread_poll_interval_ms);
+
+        // Every workspace is connected from the start so switching is instant
+        let mut workspaces: Vec<Workspace> = config.get_workspaces().iter()
+            .enumerate()
+            .map(|(index, workspace_config)| Workspace {
+                name: workspace_config.name.to_owned(),
+                team_id: None,
+                state: Some(WorkspaceState::new(workspace_config, index, page_limit, events.clone())),
+            })
+            .collect();
+        let WorkspaceState {
+            oauth_token, network, background_network, poll_network, realtime_token, loading, realtime_connected,
+            typing_users, presence, last_poll, last_unread_poll, is_polling, pending_unread, unread_poll_offset,
+            last_seen, unread, channel_list, user_list,
+            user_directory, selected_channel_index, selected_user_index, messages_list,
+            messages_cursor, selected_message_ts, messages_scroll, new_messages_below,
+            thread_ts, thread_messages, thread_scroll, reply_in_thread, reply_broadcast,
+            custom_emoji, is_emoji_requested, active_conversation_id, active_conversation_name,
+            identity, auth_error,
+        } = workspaces.first_mut()
+            .and_then(|workspace| workspace.state.take())
+            .expect("Validated config has a workspace expect");
+
+        let time_format = config.time_format;
+        let message_group_window = Duration::from_secs(60 * config.message_group_minutes);
+        let keymap = config.get_keymap().clone();
+
+        // Messages from before startup count as read
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let startup_ts = format!("{}.000000", now.as_secs());
 
-        Self { 
-            config: config,
-            oauth_token: oauth_token.to_string(),
+        let mut app = Self {
+            oauth_token,
+            network,
+            background_network,
+            poll_network,
+            realtime_token,
+            events,
+            loading,
+            spinner_index: 0,
+            realtime_connected,
+            typing_users,
+            presence,
+            poll_interval,
+            unread_poll_interval,
+            last_poll,
+            last_unread_poll,
+            is_polling,
+            pending_unread,
+            unread_poll_offset,
+            last_seen,
+            startup_ts,
+            unread,
             active_block: ActiveBlock::None,
             hovered_block: ActiveBlock::Channels,
-            team_list: Vec::from(["test team"]),
-            // TODO:: Move get channels, users, teams and messages outside of constructor
-            channel_list: Vec::from(channel_interface::get_channel_list(
-                &oauth_token).expect("Get channel list expect")),
-            user_list: Vec::from(user_interface::get_user_list(
-                &oauth_token).expect("Get user list expect")),
-            selected_team_index: None,
-            selected_channel_index: None,
-            selected_user_index: None,
-            messages_list: Vec::new(),
+            workspaces,
+            active_workspace: 0,
+            is_background: false,
+            channel_list,
+            user_list,
+            user_directory,
+            selected_team_index: Some(0),
+            selected_channel_index,
+            selected_user_index,
+            messages_list,
+            messages_cursor,
+            selected_message_ts,
+            messages_scroll,
+            new_messages_below,
+            thread_ts,
+            thread_messages,
+            thread_scroll,
+            reply_in_thread,
+            reply_broadcast,
+            emoji_picker: None,
+            editing_ts: None,
+            pending_delete: None,
+            custom_emoji,
+            is_emoji_requested,
+            time_format,
+            message_group_window,
+            keymap,
             // input_reciever: InputReciever::new(rx), 
-            active_conversation_id: None,
-            active_conversation_name: None,
+            active_conversation_id,
+            active_conversation_name,
+            identity,
+            auth_error,
             input: vec![],
             input_idx: 0,
             input_cursor_position: 0,
             input_cursor_horizontal_offset: 0,
             input_cursor_vertical_offset: 0,
+            // Unknown keys in the config are most likely typos
+            error_message: match warnings.is_empty() {
+                true => None,
+                false => Some(warnings.join(", ")),
+            },
+        };
+
+        for index in 0..app.workspaces.len() {
+            app.with_workspace(index, |app| app.test_auth());
+        }
+        app
+    }
+
+    // Swap the state of a workspace with the one in App
+    fn swap_state(&mut self, state: &mut WorkspaceState){
+        std::mem::swap(&mut self.oauth_token, &mut state.oauth_token);
+        std::mem::swap(&mut self.network, &mut state.network);
+        std::mem::swap(&mut self.background_network, &mut state.background_network);
+        std::mem::swap(&mut self.poll_network, &mut state.poll_network);
+        std::mem::swap(&mut self.realtime_token, &mut state.realtime_token);
+        std::mem::swap(&mut self.loading, &mut state.loading);
+        std::mem::swap(&mut self.realtime_connected, &mut state.realtime_connected);
+        std::mem::swap(&mut self.typing_users, &mut state.typing_users);
+        std::mem::swap(&mut self.presence, &mut state.presence);
+        std::mem::swap(&mut self.last_poll, &mut state.last_poll);
+        std::mem::swap(&mut self.last_unread_poll, &mut state.last_unread_poll);
+        std::mem::swap(&mut self.is_polling, &mut state.is_polling);
+        std::mem::swap(&mut self.pending_unread, &mut state.pending_unread);
+        std::mem::swap(&mut self.unread_poll_offset, &mut state.unread_poll_offset);
+        std::mem::swap(&mut self.last_seen, &mut state.last_seen);
+        std::mem::swap(&mut self.unread, &mut state.unread);
+        std::mem::swap(&mut self.channel_list, &mut state.channel_list);
+        std::mem::swap(&mut self.user_list, &mut state.user_list);
+        std::mem::swap(&mut self.user_directory, &mut state.user_directory);
+        std::mem::swap(&mut self.selected_channel_index, &mut state.selected_channel_index);
+        std::mem::swap(&mut self.selected_user_index, &mut state.selected_user_index);
+        std::mem::swap(&mut self.messages_list, &mut state.messages_list);
+        std::mem::swap(&mut self.messages_cursor, &mut state.messages_cursor);
+        std::mem::swap(&mut self.selected_message_ts, &mut state.selected_message_ts);
+        std::mem::swap(&mut self.messages_scroll, &mut state.messages_scroll);
+        std::mem::swap(&mut self.new_messages_below, &mut state.new_messages_below);
+        std::mem::swap(&mut self.thread_ts, &mut state.thread_ts);
+        std::mem::swap(&mut self.thread_messages, &mut state.thread_messages);
+        std::mem::swap(&mut self.thread_scroll, &mut state.thread_scroll);
+        std::mem::swap(&mut self.reply_in_thread, &mut state.reply_in_thread);
+        std::mem::swap(&mut self.reply_broadcast, &mut state.reply_broadcast);
+        std::mem::swap(&mut self.custom_emoji, &mut state.custom_emoji);
+        std::mem::swap(&mut self.is_emoji_requested, &mut state.is_emoji_requested);
+        std::mem::swap(&mut self.active_conversation_id, &mut state.active_conversation_id);
+        std::mem::swap(&mut self.active_conversation_name, &mut state.active_conversation_name);
+        std::mem::swap(&mut self.identity, &mut state.identity);
+        std::mem::swap(&mut self.auth_error, &mut state.auth_error);
+    }
+
+    // Show another workspace, its state is kept up to date in the background so
+    // nothing has to be loaded
+    pub fn switch_workspace(&mut self, index: usize){
+        if index == self.active_workspace {
+            return;
+        }
+        let Some(mut state) = self.workspaces.get_mut(index).and_then(|w| w.state.take()) else {
+            return;
+        };
+        // The input and popups belong to the conversation that is left
+        self.cancel_edit();
+        self.pending_delete = None;
+        self.emoji_picker = None;
+
+        self.swap_state(&mut state);
+        self.workspaces[self.active_workspace].state = Some(state);
+        self.active_workspace = index;
+    }
+
+    // Handle an event of a workspace. The state of a workspace that is not shown is
+    // swapped in while `handle` runs
+    pub fn with_workspace(&mut self, index: usize, handle: impl FnOnce(&mut Self)){
+        if index == self.active_workspace {
+            handle(self);
+            return;
+        }
+        let Some(mut state) = self.workspaces.get_mut(index).and_then(|w| w.state.take()) else {
+            return;
+        };
+        let shown_workspace = self.active_workspace;
+        self.swap_state(&mut state);
+        self.active_workspace = index;
+        self.is_background = true;
+
+        handle(self);
+
+        self.is_background = false;
+        self.active_workspace = shown_workspace;
+        self.swap_state(&mut state);
+        self.workspaces[index].state = Some(state);
+    }
+
+    // Unread messages of all conversations in a workspace
+    pub fn get_workspace_unread(&self, index: usize) -> Unread {
+        let unread = match self.workspaces.get(index).and_then(|w| w.state.as_ref()) {
+            Some(state) => &state.unread,
+            None => &self.unread,
+        };
+        unread.values().fold(Unread::default(), |total, unread| Unread {
+            count: total.count + unread.count,
+            mentions: total.mentions + unread.mentions,
+            capped: total.capped || unread.capped,
+        })
+    }
+
+    pub fn has_auth_error(&self, index: usize) -> bool {
+        match self.workspaces.get(index).and_then(|w| w.state.as_ref()) {
+            Some(state) => state.auth_error.is_some(),
+            None => self.auth_error.is_some(),
+        }
+    }
+
+    // Send a request to the network worker and mark `block` as loading until it responds
+    fn dispatch(&mut self, request: ApiRequest, block: ActiveBlock){
+        if self.network.send(request).is_ok() {
+            *self.loading.entry(block).or_insert(0) += 1;
+        }
+    }
+
+    fn finish_loading(&mut self, block: ActiveBlock){
+        if let Some(count) = self.loading.get_mut(&block) {
+            *count -= 1;
+            if *count == 0 {
+                self.loading.remove(&block);
+            }
+        }
+    }
+
+    pub fn is_loading(&self, block: ActiveBlock) -> bool {
+        self.loading.contains_key(&block)
+    }
+
+    pub fn on_tick(&mut self){
+        if !self.loading.is_empty() {
+            self.spinner_index = self.spinner_index.wrapping_add(1);
+        }
+        // Workspaces that are not shown poll as well, so their unread counts stay current
+        for index in 0..self.workspaces.len() {
+            self.with_workspace(index, |app| app.tick_workspace());
+        }
+    }
+
+    fn tick_workspace(&mut self){
+        self.typing_users.retain(|_, last_event| last_event.elapsed() < TYPING_TIMEOUT);
+
+        // Polling with a rejected token would only fail
+        if !self.realtime_connected && self.auth_error.is_none() {
+            if self.last_poll.elapsed() >= self.poll_interval {
+                self.poll_messages();
+            }
+            if self.last_unread_poll.elapsed() >= self.unread_poll_interval {
+                self.poll_unread();
+            }
+        }
+    }
+
+    // Get messages newer than the newest one shown in the active conversation
+    fn poll_messages(&mut self){
+        self.last_poll = Instant::now();
+        if self.is_polling || self.is_loading(ActiveBlock::Messages) {
+            return;
+        }
+        let Some(conversation_id) = &self.active_conversation_id else {
+            return;
+        };
+
+        let request = ApiRequest::PollMessages {
+            conversation_id: conversation_id.to_owned(),
+            oldest: self.get_last_seen(conversation_id).to_owned(),
+        };
+        self.is_polling = self.poll_network.send(request).is_ok();
+
+        // History does not include replies, reload the open thread as well
+        if let (Some(thread_ts), false) = (&self.thread_ts, self.is_loading(ActiveBlock::Thread)) {
+            let _ = self.poll_network.send(ApiRequest::LoadThread {
+                conversation_id: conversation_id.to_owned(),
+                thread_ts: thread_ts.to_owned(),
+            });
+        }
+    }
+
+    // Count the messages that have not been seen in the other conversations. Each round
+    // checks the next few conversations, so the number of requests does not grow with the
+    // number of channels. A round is skipped while the last one is still being checked, so
+    // the queue does not grow
+    fn poll_unread(&mut self){
+        self.last_unread_poll = Instant::now();
+        if self.pending_unread > 0 {
+            return;
+        }
+        let channels = self.channel_list.iter()
+            .filter(|c| c.is_member && !c.is_archived && !self.is_active_conversation(&c.id))
+            .collect::<Vec<_>>();
+        if channels.is_empty() {
+            return;
         }
+        let start = self.unread_poll_offset % channels.len();
+        self.unread_poll_offset = start + UNREAD_POLL_BATCH;
+        for channel in channels.iter().cycle().skip(start).take(UNREAD_POLL_BATCH.min(channels.len())) {
+            let request = ApiRequest::CheckUnread {
+                conversation_id: channel.id.to_owned(),
+                oldest: self.get_last_seen(&channel.id).to_owned(),
+            };
+            if self.background_network.send(request).is_ok() {
+                self.pending_unread += 1;
+            }
+        }
+    }
+
+    // Get the unread messages of every conversation the user is in
+    fn load_read_states(&mut self){
+        if self.pending_unread > 0 {
+            return;
+        }
+        for channel in &self.channel_list {
+            if channel.is_member && !channel.is_archived {
+                let request = ApiRequest::LoadReadState {
+                    conversation_id: channel.id.to_owned(),
+                };
+                if self.background_network.send(request).is_ok() {
+                    self.pending_unread += 1;
+                }
+            }
+        }
+    }
+
+    fn get_last_seen(&self, conversation_id: &str) -> &str {
+        self.last_seen.get(conversation_id).unwrap_or(&self.startup_ts)
+    }
+
+    // Remember the newest message of the active conversation as seen, and mark it as read
+    // in Slack so other clients show it as read as well
+    fn mark_seen(&mut self){
+        let Some(conversation_id) = self.active_conversation_id.clone() else {
+            return;
+        };
+        // Nothing is seen in a workspace that is not shown
+        if self.is_background {
+            return;
+        }
+        self.unread.remove(&conversation_id);
+        let Some(newest) = self.messages_list.first() else {
+            return;
+        };
+        let is_unseen = self.last_seen.get(&conversation_id)
+            .is_none_or(|last_seen| is_newer(&newest.ts, last_seen));
+        if is_unseen {
+            self.last_seen.insert(conversation_id.clone(), newest.ts.clone());
+            let _ = self.background_network.send(ApiRequest::MarkRead {
+                conversation_id,
+                ts: newest.ts.clone(),
+            });
+        }
+    }
+
+    // Set the last seen message unless a newer one has been seen
+    fn set_last_seen(&mut self, conversation_id: &str, ts: &str){
+        let last_seen = self.last_seen.entry(conversation_id.to_owned())
+            .or_insert_with(|| ts.to_owned());
+        if is_newer(ts, last_seen) {
+            *last_seen = ts.to_owned();
+        }
+    }
+
+    // Count messages in a conversation that is not being viewed. Messages in IMs and
+    // messages for everyone in the conversation are mentions
+    fn add_unread(&mut self, conversation_id: &str, messages: &[messages_interface::Message]){
+        let is_im = self.channel_list.iter()
+            .any(|c| c.id == conversation_id && c.kind == channel_interface::ChannelKind::Im);
+        let user_id = self.identity.as_ref().map(|identity| identity.user_id.clone());
+        // Replies only show up in the channel when they are also sent to it, and messages
+        // sent by the user are already read
+        let messages = messages.iter()
+            .filter(|m| !m.is_thread_reply() || m.is_broadcast())
+            .filter(|m| !self.is_own_message(m))
+            .collect::<Vec<_>>();
+        for message in messages {
+            let is_mention = mrkdwn::has_broadcast_mention(&message.text)
+                || user_id.as_deref().is_some_and(|user_id| mrkdwn::has_user_mention(&message.text, user_id));
+            let unread = self.unread.entry(conversation_id.to_owned()).or_default();
+            unread.count += 1;
+            if is_im || is_mention {
+                unread.mentions += 1;
+            }
+        }
+    }
+
+    // The unread messages of the IM with a user
+    pub fn get_direct_message_unread(&self, user_id: &str) -> Option<&Unread> {
+        self.channel_list.iter()
+            .find(|c| c.user.as_deref() == Some(user_id))
+            .and_then(|c| self.unread.get(&c.id))
+    }
+
+    // Look up the users in loaded messages that are not in the user list
+    fn lookup_unknown_users(&mut self){
+        // Wait for the user list, most authors will be in it
+        if self.is_loading(ActiveBlock::Users) {
+            return;
+        }
+
+        // Authors, mentioned users and the other users of IMs
+        let messages = || self.messages_list.iter().chain(&self.thread_messages);
+        let mentions: Vec<String> = messages()
+            .flat_map(|m| mrkdwn::get_user_mentions(&m.text))
+            .collect();
+        let user_ids = messages()
+            .map(|m| m.username.as_str())
+            .chain(mentions.iter().map(|id| id.as_str()))
+            .chain(self.channel_list.iter().filter_map(|c| c.user.as_deref()));
+        for user_id in self.user_directory.take_unknown(user_ids) {
+            let _ = self.background_network.send(ApiRequest::LookupUser { user_id });
+        }
+    }
+
+    // Count a new reply on its parent and show it in the open thread. Broadcast replies
+    // are shown in the channel as well
+    fn add_thread_reply(&mut self, message: messages_interface::Message){
+        let is_new = !self.thread_messages.iter().any(|m| m.ts == message.ts);
+        if let Some(parent) = self.messages_list.iter_mut()
+            .find(|m| Some(&m.ts) == message.thread_ts.as_ref()) {
+            if is_new {
+                parent.reply_count += 1;
+                if !parent.reply_users.contains(&message.username) {
+                    parent.reply_users.push(message.username.clone());
+                }
+            }
+            // The first reply turns a message into a thread parent
+            parent.thread_ts.get_or_insert_with(|| parent.ts.clone());
+        }
+
+        if message.is_broadcast() {
+            self.add_new_messages(vec![message.clone()]);
+        }
+        if self.thread_ts.is_some() && self.thread_ts == message.thread_ts {
+            merge_messages(&mut self.thread_messages, vec![message]);
+        }
+    }
+
+    // Names of the users typing in the active conversation
+    pub fn get_typing_users(&self) -> Vec<&str> {
+        let Some(conversation_id) = &self.active_conversation_id else {
+            return Vec::new();
+        };
+        self.typing_users.keys()
+            .filter(|(channel, _)| channel == conversation_id)
+            .map(|(_, user)| self.user_directory.get_display_name(user))
+            .collect()
+    }
+
+    pub fn is_active_conversation(&self, conversation_id: &str) -> bool {
+        self.active_conversation_id.as_deref() == Some(conversation_id)
+    }
+
+    // Check the token before loading anything, the workspace is loaded once it is accepted
+    fn test_auth(&mut self){
+        self.dispatch(ApiRequest::TestAuth, ActiveBlock::Channels);
+    }
+
+    fn is_own_message(&self, message: &messages_interface::Message) -> bool {
+        self.identity.as_ref().is_some_and(|identity| identity.user_id == message.username)
+    }
+
+    // Get the name of the workspace, the one in the config is used until then
+    fn load_team(&mut self){
+        let _ = self.background_network.send(ApiRequest::LoadTeam);
+    }
+
+    pub fn load_channels(&mut self){
+        self.dispatch(ApiRequest::LoadChannels, ActiveBlock::Channels);
+    }
+
+    pub fn load_users(&mut self){
+        self.dispatch(ApiRequest::LoadUsers, ActiveBlock::Users);
     }
 
-    pub fn change_conversation(&mut self, conversation_id: &String, conversation_name: &String){
+    // Show an error in the UI instead of crashing
+    pub fn set_error(&mut self, error: SlackError){
+        self.error_message = Some(error.to_string());
+    }
+
+    pub fn clear_error(&mut self){
+        self.error_message = None;
+    }
+
+    pub fn change_conversation(&mut self, conversation_id: &str, conversation_name: &str){
         // TODO: Make sure not to change to the same conversation
 
         self.active_conversation_id = Some(conversation_id.to_owned());
         self.active_conversation_name = Some(conversation_name.to_owned());
 
-        // TODO: Make sure to be able to get user conversation as well
-        self.messages_list = messages_interface::get_channel_messages(
-            conversation_id, &self.oauth_token).expect("Get messages expect");
+        self.messages_list.clear();
+        self.messages_cursor = None;
+        self.scroll_to_bottom();
+        self.cancel_edit();
+        self.pending_delete = None;
+        self.close_thread();
+        self.dispatch(ApiRequest::LoadMessages {
+            conversation_id: conversation_id.to_owned(),
+        }, ActiveBlock::Messages);
+    }
+
+    // The name shown for a conversation, IMs and group DMs are named after their users
+    pub fn get_conversation_name(&self, channel: &channel_interface::Channel) -> String {
+        match channel.kind {
+            channel_interface::ChannelKind::Im => self.user_directory
+                .get_display_name(&channel.name).to_string(),
+            channel_interface::ChannelKind::Mpim => channel_interface::get_mpim_members(&channel.name)
+                .into_iter()
+                .map(|name| self.user_list.iter()
+                    .find(|user| user.name == name)
+                    .map(|user| user.get_display_name())
+                    .unwrap_or(name))
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => channel.name.to_owned(),
+        }
+    }
+
+    // Open or create the direct message conversation with a user and show it
+    pub fn open_direct_message(&mut self, user_id: &str, user_name: &str){
+        self.dispatch(ApiRequest::OpenDirectMessage {
+            user_id: user_id.to_owned(),
+            user_name: user_name.to_owned(),
+        }, ActiveBlock::Messages);
+    }
+
+    // Load the next page of older messages, called when scrolling past the oldest message
+    pub fn load_older_messages(&mut self){
+        if self.is_loading(ActiveBlock::Messages) {
+            return;
+        }
+        let (Some(conversation_id), Some(cursor)) =
+            (&self.active_conversation_id, &self.messages_cursor) else {
+            return;
+        };
+
+        let request = ApiRequest::LoadOlderMessages {
+            conversation_id: conversation_id.to_owned(),
+            cursor: cursor.to_owned(),
+        };
+        self.dispatch(request, ActiveBlock::Messages);
+    }
+
+    // The message actions apply to, the newest one when nothing is selected
+    pub fn selected_message(&self) -> Option<&messages_interface::Message> {
+        self.get_selected_message_index()
+            .and_then(|index| self.messages_list.get(index))
+            .or_else(|| self.messages_list.first())
+    }
+
+    // Index in messages_list, which is newest first
+    fn get_selected_message_index(&self) -> Option<usize> {
+        let selected_ts = self.selected_message_ts.as_ref()?;
+        self.messages_list.iter().position(|m| &m.ts == selected_ts)
+    }
+
+    fn select_message_index(&mut self, index: usize){
+        self.selected_message_ts = self.messages_list.get(index).map(|m| m.ts.clone());
+    }
+
+    // Move the selection `count` messages back in time, loading older messages when
+    // it reaches the oldest loaded one
+    pub fn select_older_message(&mut self, count: usize){
+        if self.messages_list.is_empty() {
+            return;
+        }
+        let oldest = self.messages_list.len() - 1;
+        let index = match self.get_selected_message_index() {
+            Some(index) => index + count,
+            // Start at the newest message
+            None => count - 1,
+        };
+        if index >= oldest {
+            self.load_older_messages();
+        }
+        self.select_message_index(index.min(oldest));
+    }
+
+    pub fn select_newer_message(&mut self, count: usize){
+        let index = self.get_selected_message_index()
+            .map(|index| index.saturating_sub(count))
+            .unwrap_or(0);
+        self.select_message_index(index);
+    }
+
+    pub fn select_oldest_message(&mut self){
+        if let Some(oldest) = self.messages_list.len().checked_sub(1) {
+            self.select_message_index(oldest);
+        }
+    }
+
+    pub fn select_newest_message(&mut self){
+        self.messages_scroll = MessagesScroll::Bottom;
+        self.select_message_index(0);
+    }
+
+    // Show the newest messages and follow new ones as they arrive
+    pub fn scroll_to_bottom(&mut self){
+        self.selected_message_ts = None;
+        self.messages_scroll = MessagesScroll::Bottom;
+        self.new_messages_below = 0;
+    }
+
+    // Merge messages that arrived in the active conversation, counting the ones that are
+    // not in view
+    fn add_new_messages(&mut self, messages: Vec<messages_interface::Message>){
+        let count = merge_messages(&mut self.messages_list, messages);
+        if self.messages_scroll != MessagesScroll::Bottom {
+            self.new_messages_below += count;
+        }
+    }
+
+    // Show the thread of the selected message next to the messages
+    pub fn open_thread(&mut self){
+        let Some(conversation_id) = self.active_conversation_id.clone() else {
+            return;
+        };
+        // Replies sent to the channel open the thread they belong to
+        let Some(thread_ts) = self.selected_message()
+            .map(|m| m.thread_ts.clone().unwrap_or_else(|| m.ts.clone())) else {
+            return;
+        };
+
+        if self.thread_ts.as_ref() != Some(&thread_ts) {
+            self.thread_messages.clear();
+            self.thread_scroll = 0;
+        }
+        self.thread_ts = Some(thread_ts.clone());
+        self.active_block = ActiveBlock::Thread;
+        self.hovered_block = ActiveBlock::Thread;
+        self.dispatch(ApiRequest::LoadThread { conversation_id, thread_ts }, ActiveBlock::Thread);
+    }
+
+    pub fn close_thread(&mut self){
+        self.thread_ts = None;
+        self.thread_messages.clear();
+        self.thread_scroll = 0;
+        self.reply_in_thread = false;
+        self.reply_broadcast = false;
+        if self.active_block == ActiveBlock::Thread {
+            self.active_block = ActiveBlock::Messages;
+        }
+        if self.hovered_block == ActiveBlock::Thread {
+            self.hovered_block = ActiveBlock::Messages;
+        }
+    }
+
+    // Focus the input to reply in the open thread
+    pub fn start_thread_reply(&mut self){
+        if self.thread_ts.is_none() {
+            return;
+        }
+        self.reply_in_thread = true;
+        self.active_block = ActiveBlock::Input;
+        self.hovered_block = ActiveBlock::Input;
+    }
+
+    // Cycle the input between the channel, the thread and the thread with "also send to channel"
+    pub fn toggle_reply_target(&mut self){
+        if self.thread_ts.is_none() {
+            return;
+        }
+        (self.reply_in_thread, self.reply_broadcast) = match (self.reply_in_thread, self.reply_broadcast) {
+            (false, _) => (true, false),
+            (true, false) => (true, true),
+            (true, true) => (false, false),
+        };
+    }
+
+    // Open the emoji picker to add a reaction to the selected message, or to remove one of
+    // its reactions
+    pub fn open_emoji_picker(&mut self, mode: PickerMode){
+        let (Some(conversation_id), Some(message)) =
+            (&self.active_conversation_id, self.selected_message()) else {
+            return;
+        };
+
+        let names: Vec<String> = match mode {
+            PickerMode::Add => emoji::STANDARD_EMOJI.iter()
+                .map(|name| name.to_string())
+                .chain(self.custom_emoji.iter().cloned())
+                .collect(),
+            PickerMode::Remove => message.reactions.iter()
+                .map(|reaction| reaction.name.clone())
+                .collect(),
+        };
+        if names.is_empty() {
+            return;
+        }
+        self.emoji_picker = Some(EmojiPicker::new(mode, conversation_id, &message.ts, names));
+
+        if mode == PickerMode::Add && !self.is_emoji_requested {
+            self.is_emoji_requested = true;
+            self.dispatch(ApiRequest::LoadEmoji, ActiveBlock::Messages);
+        }
+    }
+
+    pub fn close_emoji_picker(&mut self){
+        self.emoji_picker = None;
+    }
+
+    // Add or remove the reaction selected in the picker
+    pub fn pick_emoji(&mut self){
+        let Some(picker) = self.emoji_picker.take() else {
+            return;
+        };
+        let Some(name) = picker.get_selected() else {
+            return;
+        };
+
+        let EmojiPicker { conversation_id, ts, .. } = picker;
+        let request = match picker.mode {
+            PickerMode::Add => ApiRequest::AddReaction { conversation_id, ts, name },
+            PickerMode::Remove => ApiRequest::RemoveReaction { conversation_id, ts, name },
+        };
+        self.dispatch(request, ActiveBlock::Messages);
+    }
+
+    // Replace a message in the messages and thread lists with a newer version of it
+    fn replace_message(&mut self, message: &messages_interface::Message){
+        let lists = [&mut self.messages_list, &mut self.thread_messages];
+        for old_message in lists.into_iter().flat_map(|list| list.iter_mut())
+            .filter(|m| m.ts == message.ts) {
+            *old_message = message.clone();
+        }
+    }
+
+    // Slack only allows editing and deleting your own messages
+    fn check_own_message(&mut self, message: &messages_interface::Message, action: &str) -> bool {
+        if self.identity.is_none() {
+            self.error_message = Some(format!("Can not {} messages before signing in", action));
+        }
+        else if !self.is_own_message(message) {
+            self.error_message = Some(format!("You can only {} your own messages", action));
+        }
+        self.is_own_message(message)
+    }
+
+    // Load the selected message into the input to edit it
+    pub fn start_edit(&mut self){
+        let Some(message) = self.selected_message().cloned() else {
+            return;
+        };
+        if !self.check_own_message(&message, "edit") {
+            return;
+        }
+        let (ts, text) = (message.ts.clone(), mrkdwn::decode_entities(&message.text));
+
+        self.editing_ts = Some(ts);
+        self.set_input(&text);
+        self.active_block = ActiveBlock::Input;
+        self.hovered_block = ActiveBlock::Input;
+    }
+
+    pub fn cancel_edit(&mut self){
+        if self.editing_ts.take().is_some() {
+            self.clear_input();
+        }
+    }
+
+    // Ask for confirmation before deleting the selected message
+    pub fn start_delete(&mut self){
+        let (Some(conversation_id), Some(message)) =
+            (self.active_conversation_id.clone(), self.selected_message().cloned()) else {
+            return;
+        };
+        if self.check_own_message(&message, "delete") {
+            self.pending_delete = Some((conversation_id, message.ts));
+        }
+    }
+
+    pub fn confirm_delete(&mut self){
+        if let Some((conversation_id, ts)) = self.pending_delete.take() {
+            self.dispatch(ApiRequest::DeleteMessage { conversation_id, ts }, ActiveBlock::Messages);
+        }
+    }
+
+    pub fn cancel_delete(&mut self){
+        self.pending_delete = None;
+    }
+
+    // Send the input to the active conversation, or to the open thread. When editing
+    // the edited message is updated instead
+    pub fn send_message(&mut self){
+        let Some(conversation_id) = &self.active_conversation_id else {
+            return;
+        };
+        if self.input.is_empty() {
+            return;
+        }
+
+        if let Some(ts) = self.editing_ts.take() {
+            let request = ApiRequest::UpdateMessage {
+                conversation_id: conversation_id.to_owned(),
+                ts,
+                text: String::from_iter(&self.input),
+            };
+            self.dispatch(request, ActiveBlock::Input);
+            self.clear_input();
+            return;
+        }
+
+        let thread_ts = self.thread_ts.clone().filter(|_| self.reply_in_thread);
+        let request = ApiRequest::SendMessage {
+            conversation_id: conversation_id.to_owned(),
+            text: String::from_iter(&self.input),
+            reply_broadcast: thread_ts.is_some() && self.reply_broadcast,
+            thread_ts,
+        };
+        self.dispatch(request, ActiveBlock::Input);
+        self.clear_input();
+    }
+
+    fn clear_input(&mut self){
+        self.input.clear();
+        self.input_idx = 0;
+        self.input_cursor_position = 0;
+    }
+
+    // Replace the input with `text` and put the cursor at the end
+    fn set_input(&mut self, text: &str){
+        self.input = text.chars().collect();
+        self.input_idx = self.input.len();
+        self.input_cursor_position = self.input.iter()
+            .map(|c| util::calculate_input_width(*c))
+            .sum();
+    }
+
+    pub fn handle_realtime_event(&mut self, event: RealtimeEvent){
+        // Nothing is loaded for a workspace that could not sign in
+        if self.auth_error.is_some() {
+            return;
+        }
+        match event {
+            RealtimeEvent::Connected => self.realtime_connected = true,
+            RealtimeEvent::Disconnected(_) => self.realtime_connected = false,
+            RealtimeEvent::Message { channel, message } => {
+                self.typing_users.remove(&(channel.clone(), message.username.clone()));
+                if self.is_active_conversation(&channel) {
+                    if message.is_thread_reply() {
+                        self.add_thread_reply(message);
+                    }
+                    else{
+                        self.add_new_messages(vec![message]);
+                    }
+                    self.mark_seen();
+                    self.lookup_unknown_users();
+                }
+                else{
+                    self.add_unread(&channel, &[message]);
+                }
+            }
+            RealtimeEvent::MessageChanged { channel, message } => {
+                if self.is_active_conversation(&channel) {
+                    self.replace_message(&message);
+                }
+            }
+            RealtimeEvent::MessageDeleted { channel, ts } => {
+                if self.is_active_conversation(&channel) {
+                    self.messages_list.retain(|m| m.ts != ts);
+                    self.thread_messages.retain(|m| m.ts != ts);
+                }
+            }
+            RealtimeEvent::ReactionAdded { channel, ts, user, reaction } => {
+                if self.is_active_conversation(&channel) {
+                    for message in self.messages_list.iter_mut().chain(self.thread_messages.iter_mut())
+                        .filter(|m| m.ts == ts) {
+                        message.add_reaction(&reaction, &user);
+                    }
+                }
+            }
+            RealtimeEvent::ReactionRemoved { channel, ts, user, reaction } => {
+                if self.is_active_conversation(&channel) {
+                    for message in self.messages_list.iter_mut().chain(self.thread_messages.iter_mut())
+                        .filter(|m| m.ts == ts) {
+                        message.remove_reaction(&reaction, &user);
+                    }
+                }
+            }
+            RealtimeEvent::UserTyping { channel, user } => {
+                self.typing_users.insert((channel, user), Instant::now());
+            }
+            RealtimeEvent::PresenceChange { user, presence } => {
+                self.presence.insert(user, presence);
+            }
+            RealtimeEvent::ConversationMarked { channel, ts, unread_count } => {
+                self.set_last_seen(&channel, &ts);
+                match (unread_count, self.unread.get_mut(&channel)) {
+                    (0, _) => {
+                        self.unread.remove(&channel);
+                    }
+                    (count, Some(unread)) => {
+                        unread.count = count;
+                        unread.mentions = unread.mentions.min(count);
+                        unread.capped = false;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    pub fn handle_api_response(&mut self, response: ApiResponse){
+        match response {
+            ApiResponse::Auth(result) => {
+                self.finish_loading(ActiveBlock::Channels);
+                match result {
+                    Ok(identity) => {
+                        self.identity = Some(identity);
+                        self.auth_error = None;
+                        // A rejected token would only keep reconnecting
+                        if let Some(token) = self.realtime_token.take() {
+                            realtime::start(token, self.active_workspace, self.events.clone());
+                        }
+                    }
+                    // Nothing will load with a rejected token
+                    Err(error) if error.is_auth_error() => {
+                        self.auth_error = Some(error.to_string());
+                        return;
+                    }
+                    // The token may still be fine when Slack could not be reached
+                    Err(error) => self.set_error(error),
+                }
+                self.load_team();
+                self.load_channels();
+                self.load_users();
+            }
+            // Workspaces keep their name from the config when team.info fails
+            ApiResponse::Team(result) => {
+                if let Ok(team) = result {
+                    let workspace = &mut self.workspaces[self.active_workspace];
+                    workspace.name = team.name;
+                    workspace.team_id = Some(team.id);
+                }
+            }
+            ApiResponse::Channels(result) => {
+                self.finish_loading(ActiveBlock::Channels);
+                match result {
+                    Ok(mut channels) => {
+                        // Grouped by sidebar section with archived channels last
+                        channels.sort_by_key(|c| (c.kind.get_section(), c.is_archived));
+                        self.channel_list = channels;
+                        self.lookup_unknown_users();
+                        self.load_read_states();
+                    }
+                    Err(error) => self.set_error(error),
+                }
+            }
+            ApiResponse::Users(result) => {
+                self.finish_loading(ActiveBlock::Users);
+                match result {
+                    Ok(users) => {
+                        self.user_directory.extend(&users);
+                        self.user_list = users;
+                        self.lookup_unknown_users();
+                    }
+                    Err(error) => self.set_error(error),
+                }
+            }
+            ApiResponse::Messages { conversation_id, result } => {
+                self.finish_loading(ActiveBlock::Messages);
+                // Ignore responses for a conversation that is no longer shown
+                if !self.is_active_conversation(&conversation_id) {
+                    return;
+                }
+                match result {
+                    Ok((messages, cursor)) => {
+                        self.messages_list = messages;
+                        self.messages_cursor = cursor;
+                        self.mark_seen();
+                        self.lookup_unknown_users();
+                    }
+                    Err(error) => self.set_error(error),
+                }
+            }
+            ApiResponse::OlderMessages { conversation_id, result } => {
+                self.finish_loading(ActiveBlock::Messages);
+                if !self.is_active_conversation(&conversation_id) {
+                    return;
+                }
+                match result {
+                    Ok((messages, cursor)) => {
+                        self.messages_list.extend(messages);
+                        self.messages_cursor = cursor;
+                        self.lookup_unknown_users();
+                    }
+                    Err(error) => self.set_error(error),
+                }
+            }
+            ApiResponse::DirectMessageOpened { user_name, result } => {
+                self.finish_loading(ActiveBlock::Messages);
+                match result {
+                    Ok(conversation_id) => self.change_conversation(&conversation_id, &user_name),
+                    Err(error) => self.set_error(error),
+                }
+            }
+            ApiResponse::NewMessages { conversation_id, result } => {
+                self.is_polling = false;
+                // Polling errors are not shown, the next poll will try again
+                if let (true, Ok(messages)) = (self.is_active_conversation(&conversation_id), result) {
+                    self.add_new_messages(messages);
+                    self.mark_seen();
+                    self.lookup_unknown_users();
+                }
+            }
+            ApiResponse::Thread { conversation_id, thread_ts, result } => {
+                self.finish_loading(ActiveBlock::Thread);
+                // Ignore threads that have been closed in the meantime
+                if !self.is_active_conversation(&conversation_id)
+                    || self.thread_ts.as_ref() != Some(&thread_ts) {
+                    return;
+                }
+                match result {
+                    Ok(messages) => {
+                        self.thread_messages = messages;
+                        self.lookup_unknown_users();
+                    }
+                    Err(error) => self.set_error(error),
+                }
+            }
+            ApiResponse::UnreadChecked { conversation_id, result } => {
+                self.pending_unread = self.pending_unread.saturating_sub(1);
+                // Counted again from the last seen message
+                if let (false, Ok((messages, has_more))) = (self.is_active_conversation(&conversation_id), result) {
+                    self.unread.remove(&conversation_id);
+                    self.add_unread(&conversation_id, &messages);
+                    if let Some(unread) = self.unread.get_mut(&conversation_id) {
+                        unread.capped = has_more;
+                    }
+                }
+            }
+            ApiResponse::ReadState { conversation_id, result } => {
+                self.pending_unread = self.pending_unread.saturating_sub(1);
+                let (false, Ok((state, messages, has_more))) =
+                    (self.is_active_conversation(&conversation_id), result) else {
+                    return;
+                };
+                self.set_last_seen(&conversation_id, &state.last_read);
+                self.unread.remove(&conversation_id);
+                self.add_unread(&conversation_id, &messages);
+                // Slack's count includes messages past the first page
+                if let Some(unread) = self.unread.get_mut(&conversation_id) {
+                    match state.unread_count {
+                        Some(count) => unread.count = count,
+                        None => unread.capped = has_more,
+                    }
+                }
+            }
+            // Read state is synced in the background, it is sent again with the next message
+            ApiResponse::ReadMarked { .. } => {}
+            ApiResponse::UserInfo { user_id, result } => {
+                // Unknown users stay as their id, they are not requested again
+                if let Ok(user) = result {
+                    if user.id == user_id {
+                        self.user_directory.insert(user);
+                    }
+                }
+            }
+            ApiResponse::ReactionUpdated { conversation_id, result } => {
+                self.finish_loading(ActiveBlock::Messages);
+                match result {
+                    Ok(message) => {
+                        if self.is_active_conversation(&conversation_id) {
+                            self.replace_message(&message);
+                        }
+                    }
+                    Err(error) => self.set_error(error),
+                }
+            }
+            ApiResponse::Emoji(result) => {
+                self.finish_loading(ActiveBlock::Messages);
+                match result {
+                    Ok(names) => {
+                        if let Some(picker) = self.emoji_picker.as_mut()
+                            .filter(|picker| picker.mode == PickerMode::Add) {
+                            picker.extend_names(&names);
+                        }
+                        self.custom_emoji = names;
+                    }
+                    Err(error) => self.set_error(error),
+                }
+            }
+            ApiResponse::MessageUpdated { conversation_id, ts, text, result } => {
+                self.finish_loading(ActiveBlock::Input);
+                match result {
+                    Ok(()) => {
+                        if !self.is_active_conversation(&conversation_id) {
+                            return;
+                        }
+                        for message in self.messages_list.iter_mut().chain(self.thread_messages.iter_mut())
+                            .filter(|m| m.ts == ts) {
+                            message.text = text.clone();
+                            message.is_edited = true;
+                        }
+                    }
+                    Err(error) => {
+                        // Keep editing so the change is not lost
+                        if self.input.is_empty() && self.is_active_conversation(&conversation_id) {
+                            self.editing_ts = Some(ts);
+                            self.set_input(&text);
+                        }
+                        self.set_error(error);
+                    }
+                }
+            }
+            ApiResponse::MessageDeleted { conversation_id, ts, result } => {
+                self.finish_loading(ActiveBlock::Messages);
+                match result {
+                    Ok(()) => {
+                        if self.is_active_conversation(&conversation_id) {
+                            self.messages_list.retain(|m| m.ts != ts);
+                            self.thread_messages.retain(|m| m.ts != ts);
+                        }
+                    }
+                    Err(error) => self.set_error(error),
+                }
+            }
+            ApiResponse::MessageSent { conversation_id, text, result } => {
+                self.finish_loading(ActiveBlock::Input);
+                match result {
+                    Ok(message) => {
+                        // Show the sent message without reloading the conversation, the
+                        // real-time echo is merged into it
+                        if !self.is_active_conversation(&conversation_id) {
+                            return;
+                        }
+                        if message.is_thread_reply() {
+                            self.add_thread_reply(message);
+                        }
+                        else{
+                            // Jump to the message like Slack does
+                            self.scroll_to_bottom();
+                            self.add_new_messages(vec![message]);
+                        }
+                        self.mark_seen();
+                    }
+                    Err(error) => {
+                        // Give the text back so the message can be resent
+                        if self.input.is_empty() {
+                            self.set_input(&text);
+                        }
+                        self.set_error(error);
+                    }
+                }
+            }
+        }
     }
 }
 
-pub fn start_ui(rx: &mpsc::Receiver<InputEvent<crossterm::event::KeyEvent>>)
+// Merge messages into a list, keeping it sorted newest first without duplicates.
+// Returns the number of messages added
+fn merge_messages(list: &mut Vec<messages_interface::Message>,
+    messages: Vec<messages_interface::Message>) -> usize {
+    let mut count = 0;
+    for message in messages {
+        if list.iter().any(|m| m.ts == message.ts) {
+            continue;
+        }
+        let index = list.iter()
+            .position(|m| is_newer(&message.ts, &m.ts))
+            .unwrap_or(list.len());
+        list.insert(index, message);
+        count += 1;
+    }
+    count
+}
+
+// Compare Slack timestamps, "1512085950.000216"
+fn is_newer(ts: &str, other: &str) -> bool {
+    let parse = |ts: &str| ts.parse::<f64>().unwrap_or(0.0);
+    parse(ts) > parse(other)
+}
+
+pub fn start_ui(config: Config, warnings: Vec<String>, rx: &mpsc::Receiver<InputEvent<KeyEvent>>,
+    events: mpsc::Sender<InputEvent<KeyEvent>>)
 -> Result<(), Box<dyn std::error::Error>>{
     let stdout = io::stdout();
     let backend = CrosstermBackend::new(stdout);
@@ -119,7 +1365,7 @@ pub fn start_ui(rx: &mpsc::Receiver<InputEvent<crossterm::event::KeyEvent>>)
     let mut terminal = Terminal::new(backend)?;
     terminal.clear()?;
 
-    let mut app = App::new();
+    let mut app = App::new(config, warnings, events);
 
     let mut input_reciever = InputReciever::new(rx);
 
@@ -127,7 +1373,7 @@ pub fn start_ui(rx: &mpsc::Receiver<InputEvent<crossterm::event::KeyEvent>>)
         // Draw UI
         terminal.draw(|rect| 
             ui::draw_ui(rect,
-                &app).expect("Draw UI"),
+                &mut app).expect("Draw UI"),
         ).expect("draw ui expect");
 
         // Handle input
@@ -168,4 +1414,172 @@ pub fn update_cursor(terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
         terminal.hide_cursor()?;
     }
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::config;
+    use serde_json::json;
+    use std::fs;
+
+    const USER_ID: &str = "U012AB3CDE";
+
+    // An App for one workspace of the mock server
+    pub fn create_app(name: &str) -> App {
+        let path = std::env::temp_dir().join(format!("slacker-tui-{}-{}.json", name, std::process::id()));
+        let text = json!({
+            "api_url": slack_interface::start_mock().api_url,
+            "workspaces": [{ "name": "test", "oauth_token": "xoxp-test" }],
+        });
+        fs::write(&path, text.to_string()).expect("Write test config expect");
+        let (config, _) = config::load(Some(&path)).expect("Load test config expect");
+        let _ = fs::remove_file(&path);
+
+        let (events, _) = mpsc::channel();
+        let mut app = App::new(config, Vec::new(), events);
+        app.identity = Some(Identity {
+            user_id: USER_ID.to_string(),
+            user: "spengler".to_string(),
+            team_id: "T0GHOST01".to_string(),
+            team: "Ghostbusters".to_string(),
+            url: String::new(),
+        });
+        app
+    }
+
+    #[test]
+    fn starts_realtime_once_the_token_is_accepted() {
+        let mut app = create_app("realtime");
+        app.handle_api_response(ApiResponse::Auth(Err(SlackError::Api("invalid_auth".to_string()))));
+        assert!(app.realtime_token.is_some());
+
+        let identity = slack_interface::auth_interface::test_auth("xoxp-test");
+        app.handle_api_response(ApiResponse::Auth(identity));
+        assert!(app.auth_error.is_none());
+        assert!(app.realtime_token.is_none());
+    }
+
+    #[test]
+    fn caps_unread_count_at_the_first_page() {
+        let mut app = create_app("unread");
+        let state = channel_interface::ReadState {
+            last_read: "0000000000.000000".to_string(),
+            unread_count: None,
+        };
+        let (messages, has_more) = messages_interface::get_newest_messages(
+            "C012AB3CD", "xoxp-test", 1, &state.last_read).expect("Get newest messages expect");
+        app.handle_api_response(ApiResponse::ReadState {
+            conversation_id: "C012AB3CD".to_string(),
+            result: Ok((state, messages, has_more)),
+        });
+        assert_eq!(app.unread.get("C012AB3CD"), Some(&Unread { count: 1, mentions: 0, capped: true }));
+
+        // Slack's own count is exact
+        app.handle_realtime_event(RealtimeEvent::ConversationMarked {
+            channel: "C012AB3CD".to_string(),
+            ts: "1512104434.000490".to_string(),
+            unread_count: 2,
+        });
+        assert_eq!(app.unread.get("C012AB3CD"), Some(&Unread { count: 2, mentions: 0, capped: false }));
+    }
+
+    #[test]
+    fn polls_unread_in_turns() {
+        let mut app = create_app("poll");
+        app.channel_list = (0..25).map(|index| channel_interface::Channel {
+            id: format!("C{:02}", index),
+            name: format!("channel-{}", index),
+            kind: channel_interface::ChannelKind::Public,
+            is_member: true,
+            is_archived: false,
+            topic: String::new(),
+            purpose: String::new(),
+            num_members: 2,
+            user: None,
+        }).collect();
+        let (requests, polled) = mpsc::channel();
+        app.background_network = requests;
+        let poll = |app: &mut App| {
+            app.pending_unread = 0;
+            app.poll_unread();
+            polled.try_iter().map(|request| match request {
+                ApiRequest::CheckUnread { conversation_id, .. } => conversation_id,
+                _ => panic!("Only unread checks are sent"),
+            }).collect::<Vec<String>>()
+        };
+
+        let first = poll(&mut app);
+        assert_eq!(first.len(), UNREAD_POLL_BATCH);
+        assert_eq!(first[0], "C00");
+        // The next round starts with the channels that were left out and wraps around
+        let second = poll(&mut app);
+        assert_eq!(second.len(), UNREAD_POLL_BATCH);
+        assert_eq!(second[..6], ["C20", "C21", "C22", "C23", "C24", "C00"]);
+    }
+
+    #[test]
+    fn merges_sent_messages_without_reloading() {
+        let mut app = create_app("send");
+        let token = "xoxp-test";
+        let (messages, cursor) = messages_interface::get_channel_messages("C0ECTO001", token, 2, None)
+            .expect("Get messages expect");
+        app.active_conversation_id = Some("C0ECTO001".to_string());
+        app.messages_list = messages;
+        app.messages_cursor = cursor.clone();
+        let send = |app: &mut App, text: &str, thread_ts: Option<&str>| {
+            let result = messages_interface::send_channel_message(text, "C0ECTO001", thread_ts, false, token);
+            let ts = result.as_ref().map(|message| message.ts.clone()).expect("Send message expect");
+            app.handle_api_response(ApiResponse::MessageSent {
+                conversation_id: "C0ECTO001".to_string(),
+                text: text.to_string(),
+                result,
+            });
+            ts
+        };
+
+        let ts = send(&mut app, "Who you gonna call?", None);
+        assert_eq!(app.messages_list.len(), 3);
+        assert_eq!(app.messages_list[0].ts, ts);
+        // The older pages are still where they were
+        assert_eq!(app.messages_cursor, cursor);
+        assert_eq!(app.messages_scroll, MessagesScroll::Bottom);
+
+        app.thread_ts = Some("1512104900.000310".to_string());
+        app.thread_messages = messages_interface::get_thread_replies("C0ECTO001", "1512104900.000310", token, 10)
+            .expect("Get thread expect");
+        let ts = send(&mut app, "Ghostbusters!", Some("1512104900.000310"));
+        assert_eq!(app.thread_messages.len(), 4);
+        assert_eq!(app.thread_messages[0].ts, ts);
+        assert_eq!(app.messages_list.len(), 3);
+        assert_eq!(app.messages_list[2].reply_count, 3);
+    }
+
+    #[test]
+    fn edits_multi_line_message() {
+        let mut app = create_app("edit");
+        let text = "Cross the streams:\n```\nif streams.crossed() {\n    panic!(\"bad\");\n}\n```";
+        let message = messages_interface::parse_message(&json!({
+            "type": "message",
+            "user": USER_ID,
+            "text": text,
+            "ts": "1512104434.000490",
+        })).expect("Parse message expect");
+        app.active_conversation_id = Some("C061EG9SL".to_string());
+        app.selected_message_ts = Some(message.ts.clone());
+        app.messages_list = vec![message];
+
+        app.start_edit();
+
+        assert_eq!(app.editing_ts.as_deref(), Some("1512104434.000490"));
+        assert_eq!(app.active_block, ActiveBlock::Input);
+        assert_eq!(String::from_iter(&app.input), text);
+        assert_eq!(app.input_idx, text.chars().count());
+        // Every line break is shown as one symbol
+        assert_eq!(app.input_cursor_position as usize, text.chars().count());
+
+        app.send_message();
+        assert!(app.editing_ts.is_none());
+        assert!(app.input.is_empty());
+        assert_eq!(app.input_cursor_position, 0);
+    }
+}
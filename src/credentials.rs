@@ -0,0 +1,375 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+// Read instead of asking for the passphrase of an encrypted credentials file
+const PASSPHRASE_VAR: &str = "SLACKER_TUI_PASSPHRASE";
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const KEY_LENGTH: usize = 32;
+
+// The only key derivation written and read, argon2id version 0x13
+const KDF: &str = "argon2id";
+// Parameters of new files, also assumed for files written before they were stored
+const KDF_M_COST: u32 = 19 * 1024;
+const KDF_T_COST: u32 = 2;
+const KDF_P_COST: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum CredentialError {
+    #[error("Could not read {path}: {error}")]
+    Read { path: String, error: io::Error },
+    #[error("Could not write {path}: {error}")]
+    Write { path: String, error: io::Error },
+    #[error("Invalid credentials in {path}: {error}")]
+    Parse { path: String, error: serde_json::Error },
+    #[error("{path} can be read by other users (mode {mode:o}), run `chmod 600 {path}`")]
+    TooOpen { path: String, mode: u32 },
+    #[error("Could not decrypt {0}, is the passphrase right?")]
+    Decrypt(String),
+    #[error("Could not encrypt the credentials: {0}")]
+    Encrypt(String),
+    #[error("{path} is encrypted with the unknown key derivation '{kdf}'")]
+    UnknownKdf { path: String, kdf: String },
+    #[error("No passphrase for {0}")]
+    NoPassphrase(String),
+    #[error("The passphrases do not match")]
+    PassphraseMismatch,
+    #[error("token_command of workspace '{workspace}' failed: {message}")]
+    Command { workspace: String, message: String },
+}
+
+// The tokens of a workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credentials {
+    pub oauth_token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_token: Option<String>,
+}
+
+// Where tokens are kept, by workspace name
+pub trait CredentialStore {
+    fn get(&self, workspace: &str) -> Result<Option<Credentials>, CredentialError>;
+    fn set(&self, workspace: &str, credentials: Credentials) -> Result<(), CredentialError>;
+    fn get_path(&self) -> &Path;
+}
+
+// "credentials.backend" in the config
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    // JSON that only the user can read
+    #[default]
+    File,
+    // JSON encrypted with a key derived from a passphrase
+    Encrypted,
+}
+
+impl Backend {
+    fn get_file_name(&self) -> &'static str {
+        match self {
+            Backend::File => "credentials.json",
+            Backend::Encrypted => "credentials.enc",
+        }
+    }
+}
+
+// Open the store of `backend`, by default next to the config file
+pub fn open(backend: Backend, path: Option<&Path>, config_path: Option<&Path>) -> Box<dyn CredentialStore> {
+    let path = path.map(|path| path.to_path_buf()).unwrap_or_else(|| {
+        let directory = config_path.and_then(|path| path.parent()).unwrap_or(Path::new(""));
+        directory.join(backend.get_file_name())
+    });
+    match backend {
+        Backend::File => Box::new(FileStore { path }),
+        Backend::Encrypted => Box::new(EncryptedStore { path, passphrase: OnceCell::new() }),
+    }
+}
+
+// Run a workspace's token_command, e.g. `pass show slack/work`. The first line it prints is the token
+pub fn run_token_command(workspace: &str, command: &str) -> Result<String, CredentialError> {
+    let error = |message: String| CredentialError::Command { workspace: workspace.to_string(), message };
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("SLACKER_TUI_WORKSPACE", workspace)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|command_error| error(command_error.to_string()))?;
+    if !output.status.success() {
+        return Err(error(output.status.to_string()));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match stdout.lines().next().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+        Some(token) => Ok(token.to_string()),
+        None => Err(error("it printed no token".to_string())),
+    }
+}
+
+type CredentialMap = BTreeMap<String, Credentials>;
+
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl CredentialStore for FileStore {
+    fn get(&self, workspace: &str) -> Result<Option<Credentials>, CredentialError> {
+        Ok(read_file(&self.path)?
+            .map(|text| parse(&self.path, &text))
+            .transpose()?
+            .and_then(|mut credentials| credentials.remove(workspace)))
+    }
+
+    fn set(&self, workspace: &str, credentials: Credentials) -> Result<(), CredentialError> {
+        let mut map = match read_file(&self.path)? {
+            Some(text) => parse(&self.path, &text)?,
+            None => CredentialMap::new(),
+        };
+        map.insert(workspace.to_string(), credentials);
+        let text = serde_json::to_string_pretty(&map).expect("Serialize credentials expect");
+        write_file(&self.path, &(text + "\n"))
+    }
+
+    fn get_path(&self) -> &Path {
+        &self.path
+    }
+}
+
+// The file is only decrypted when a token is needed, the passphrase is asked for once
+pub struct EncryptedStore {
+    path: PathBuf,
+    passphrase: OnceCell<String>,
+}
+
+// The encrypted file, salt, nonce and ciphertext are base64
+#[derive(Serialize, Deserialize)]
+struct EncryptedFile {
+    kdf: String,
+    #[serde(default)]
+    kdf_params: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// Argon2 costs, kept in the file so that it can be read when the defaults change
+#[derive(Serialize, Deserialize)]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams { m_cost: KDF_M_COST, t_cost: KDF_T_COST, p_cost: KDF_P_COST }
+    }
+}
+
+impl EncryptedStore {
+    fn get_passphrase(&self, confirm: bool) -> Result<&str, CredentialError> {
+        if let Some(passphrase) = self.passphrase.get() {
+            return Ok(passphrase);
+        }
+
+        let path_name = self.path.display().to_string();
+        let passphrase = match env::var(PASSPHRASE_VAR).ok().filter(|passphrase| !passphrase.is_empty()) {
+            Some(passphrase) => passphrase,
+            None => {
+                let prompt = |prompt: String| rpassword::prompt_password(prompt)
+                    .map_err(|_| CredentialError::NoPassphrase(path_name.clone()));
+                let passphrase = prompt(format!("Passphrase for {}: ", path_name))?;
+                // A new file is encrypted with it, so a typo would lock the user out
+                if confirm && prompt("Repeat the passphrase: ".to_string())? != passphrase {
+                    return Err(CredentialError::PassphraseMismatch);
+                }
+                passphrase
+            }
+        };
+        if passphrase.is_empty() {
+            return Err(CredentialError::NoPassphrase(path_name));
+        }
+        Ok(self.passphrase.get_or_init(|| passphrase))
+    }
+
+    fn decrypt(&self, text: &str) -> Result<CredentialMap, CredentialError> {
+        let path_name = self.path.display().to_string();
+        let file: EncryptedFile = serde_json::from_str(text)
+            .map_err(|error| CredentialError::Parse { path: path_name.clone(), error })?;
+        if file.kdf != KDF {
+            return Err(CredentialError::UnknownKdf { path: path_name, kdf: file.kdf });
+        }
+
+        let decode = |value: &str| BASE64.decode(value).map_err(|_| CredentialError::Decrypt(path_name.clone()));
+        let salt = decode(&file.salt)?;
+        let nonce = decode(&file.nonce)?;
+        let ciphertext = decode(&file.ciphertext)?;
+        if nonce.len() != NONCE_LENGTH {
+            return Err(CredentialError::Decrypt(path_name));
+        }
+
+        let cipher = get_cipher(self.get_passphrase(false)?, &salt, &file.kdf_params)
+            .map_err(|_| CredentialError::Decrypt(path_name.clone()))?;
+        let plaintext = Zeroizing::new(cipher.decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| CredentialError::Decrypt(path_name))?);
+        parse(&self.path, &String::from_utf8_lossy(&plaintext))
+    }
+
+    fn encrypt(&self, map: &CredentialMap) -> Result<String, CredentialError> {
+        let mut salt = [0; SALT_LENGTH];
+        let mut nonce = [0; NONCE_LENGTH];
+        getrandom::getrandom(&mut salt).map_err(|error| CredentialError::Encrypt(error.to_string()))?;
+        getrandom::getrandom(&mut nonce).map_err(|error| CredentialError::Encrypt(error.to_string()))?;
+
+        let plaintext = Zeroizing::new(serde_json::to_vec(map).expect("Serialize credentials expect"));
+        let kdf_params = KdfParams::default();
+        let cipher = get_cipher(self.get_passphrase(true)?, &salt, &kdf_params)
+            .map_err(|error| CredentialError::Encrypt(error.to_string()))?;
+        let ciphertext = cipher.encrypt(XNonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|error| CredentialError::Encrypt(error.to_string()))?;
+
+        let file = EncryptedFile {
+            kdf: KDF.to_string(),
+            kdf_params,
+            salt: BASE64.encode(salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        Ok(serde_json::to_string_pretty(&file).expect("Serialize encrypted credentials expect") + "\n")
+    }
+}
+
+impl CredentialStore for EncryptedStore {
+    fn get(&self, workspace: &str) -> Result<Option<Credentials>, CredentialError> {
+        Ok(read_file(&self.path)?
+            .map(|text| self.decrypt(&text))
+            .transpose()?
+            .and_then(|mut credentials| credentials.remove(workspace)))
+    }
+
+    fn set(&self, workspace: &str, credentials: Credentials) -> Result<(), CredentialError> {
+        let mut map = match read_file(&self.path)? {
+            Some(text) => self.decrypt(&text)?,
+            None => CredentialMap::new(),
+        };
+        map.insert(workspace.to_string(), credentials);
+        write_file(&self.path, &self.encrypt(&map)?)
+    }
+
+    fn get_path(&self) -> &Path {
+        &self.path
+    }
+}
+
+// The derived key is wiped once the cipher, which wipes its own copy when dropped, is made
+fn get_cipher(passphrase: &str, salt: &[u8], kdf_params: &KdfParams) -> Result<XChaCha20Poly1305, argon2::Error> {
+    let params = Params::new(kdf_params.m_cost, kdf_params.t_cost, kdf_params.p_cost, Some(KEY_LENGTH))?;
+    let mut key = Zeroizing::new([0; KEY_LENGTH]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())?;
+    Ok(XChaCha20Poly1305::new(Key::from_slice(key.as_ref())))
+}
+
+fn parse(path: &Path, text: &str) -> Result<CredentialMap, CredentialError> {
+    serde_json::from_str(text)
+        .map_err(|error| CredentialError::Parse { path: path.display().to_string(), error })
+}
+
+// Read a credentials file, None if there is none yet. Files other users can read are refused
+fn read_file(path: &Path) -> Result<Option<String>, CredentialError> {
+    let path_name = path.display().to_string();
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(CredentialError::Read { path: path_name, error }),
+    };
+    check_permissions(&path_name, &metadata)?;
+
+    fs::read_to_string(path)
+        .map(Some)
+        .map_err(|error| CredentialError::Read { path: path_name, error })
+}
+
+#[cfg(unix)]
+fn check_permissions(path_name: &str, metadata: &fs::Metadata) -> Result<(), CredentialError> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        return Err(CredentialError::TooOpen { path: path_name.to_string(), mode });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path_name: &str, _metadata: &fs::Metadata) -> Result<(), CredentialError> {
+    Ok(())
+}
+
+// Replace the file with one only the user can read and write
+fn write_file(path: &Path, text: &str) -> Result<(), CredentialError> {
+    let write = || -> io::Result<()> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        // A left over file would keep its mode
+        let temporary_path = path.with_extension("tmp");
+        let _ = fs::remove_file(&temporary_path);
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut file = options.open(&temporary_path)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temporary_path, path)
+    };
+    write().map_err(|error| CredentialError::Write { path: path.display().to_string(), error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_store() -> EncryptedStore {
+        let passphrase = OnceCell::new();
+        passphrase.set("correct horse".to_string()).expect("Set passphrase expect");
+        EncryptedStore { path: PathBuf::from("credentials.enc"), passphrase }
+    }
+
+    #[test]
+    fn decrypts_with_the_stored_kdf() {
+        let store = get_store();
+        let mut map = CredentialMap::new();
+        map.insert("work".to_string(), Credentials { oauth_token: "xoxp-one".to_string(), app_token: None });
+        let text = store.encrypt(&map).expect("Encrypt expect");
+        assert_eq!(store.decrypt(&text).expect("Decrypt expect")["work"].oauth_token, "xoxp-one");
+
+        // Files written before the costs were stored use the defaults of then
+        let mut file: serde_json::Value = serde_json::from_str(&text).expect("Parse expect");
+        file.as_object_mut().expect("Object expect").remove("kdf_params");
+        assert!(store.decrypt(&file.to_string()).is_ok());
+
+        file["kdf"] = "scrypt".into();
+        assert!(matches!(store.decrypt(&file.to_string()), Err(CredentialError::UnknownKdf { .. })));
+
+        file["kdf"] = KDF.into();
+        file["kdf_params"] = serde_json::json!({ "m_cost": KDF_M_COST, "t_cost": 3, "p_cost": KDF_P_COST });
+        assert!(matches!(store.decrypt(&file.to_string()), Err(CredentialError::Decrypt(_))));
+    }
+}
@@ -0,0 +1,87 @@
+// Emoji names for the reaction picker, custom workspace emoji are added from emoji.list
+
+// Common standard emoji by Slack name
+pub const STANDARD_EMOJI: [&str; 64] = [
+    "+1", "-1", "heart", "joy", "smile", "slightly_smiling_face", "grinning", "laughing",
+    "sweat_smile", "wink", "blush", "heart_eyes", "sunglasses", "thinking_face",
+    "upside_down_face", "neutral_face", "confused", "disappointed", "cry", "sob", "scream",
+    "rage", "sleeping", "face_palm", "shrug", "exploding_head", "skull", "ghost", "eyes",
+    "wave", "clap", "raised_hands", "pray", "muscle", "ok_hand", "point_up", "v", "handshake",
+    "fire", "tada", "rocket", "star", "sparkles", "zap", "boom", "100", "white_check_mark",
+    "heavy_check_mark", "x", "warning", "question", "exclamation", "bulb", "memo", "bug",
+    "coffee", "beers", "pizza", "cake", "trophy", "dart", "hourglass", "lock", "link",
+];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PickerMode {
+    Add,
+    // Only the reactions already on the message can be picked
+    Remove,
+}
+
+// Searchable list of emoji names for reacting to the message with `ts`
+pub struct EmojiPicker{
+    pub mode: PickerMode,
+    pub conversation_id: String,
+    pub ts: String,
+    pub query: String,
+    pub selected: usize,
+    names: Vec<String>,
+}
+
+impl EmojiPicker{
+    pub fn new(mode: PickerMode, conversation_id: &str, ts: &str, names: Vec<String>) -> Self {
+        EmojiPicker{
+            mode,
+            conversation_id: conversation_id.to_string(),
+            ts: ts.to_string(),
+            query: String::new(),
+            selected: 0,
+            names,
+        }
+    }
+
+    // Add names that were not known when the picker was opened
+    pub fn extend_names(&mut self, names: &[String]){
+        for name in names {
+            if !self.names.contains(name) {
+                self.names.push(name.clone());
+            }
+        }
+    }
+
+    // Names containing the query, the ones starting with it first
+    pub fn get_matches(&self) -> Vec<&str> {
+        let query = self.query.to_lowercase();
+        let (mut matches, contains): (Vec<&str>, Vec<&str>) = self.names.iter()
+            .map(|name| name.as_str())
+            .filter(|name| name.contains(&query))
+            .partition(|name| name.starts_with(&query));
+        matches.extend(contains);
+        matches
+    }
+
+    pub fn get_selected(&self) -> Option<String> {
+        self.get_matches().get(self.selected).map(|name| name.to_string())
+    }
+
+    pub fn push_char(&mut self, c: char){
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop_char(&mut self){
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    pub fn select_previous(&mut self){
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self){
+        if self.selected + 1 < self.get_matches().len() {
+            self.selected += 1;
+        }
+    }
+}
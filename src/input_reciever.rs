@@ -1,16 +1,11 @@
 // use chrono::prelude::*;
-use crossterm::{
-    event::{self, Event as CEvent, KeyCode, KeyEvent, KeyModifiers},
-    terminal::{disable_raw_mode, enable_raw_mode},
-};
-use tui::{
-    Terminal, widgets::ListState,
-};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use std::sync::mpsc;
 
-use crate::{app::{App, ActiveBlock}, slack_interface::messages_interface};
-use crate::{InputEvent};
-use crate::slack_interface::{user_interface::User, channel_interface::Channel};
+use crate::app::{App, ActiveBlock, MESSAGES_PAGE_SIZE};
+use crate::emoji::PickerMode;
+use crate::keymap::{Action, Context};
+use crate::InputEvent;
 use crate::util;
 
 pub struct InputReciever<'a> {
@@ -29,130 +24,260 @@ impl<'a> InputReciever<'a> {
     pub fn handle_input(&mut self, app: &mut App) -> Result<InputEvent<()>, Box<dyn std::error::Error>>{
         // Receive event from input thread
         match self.rx.recv()? {
-            InputEvent::Input(event) => match event {
-                // Priority key presses
-                // Quit software is user presses 'q'
-                KeyEvent{ code: KeyCode::Char('q'), modifiers: KeyModifiers::NONE} => {
-                    return Ok(InputEvent::Quit);
-                }
-                // Deselect focused window
-                KeyEvent { code: KeyCode::Esc, modifiers: KeyModifiers::NONE } => {
-                    // TODO: Move this to a function
-                    app.active_block.clone_from(&ActiveBlock::None);
-                    // TODO: Improve to only use one selected index
-                    // app.selected_team_index = None;
-                    // app.selected_channel_index = None;
-                    // app.selected_user_index = None;
+            // The emoji picker takes all keys while it is open, so the search can contain 'q'
+            InputEvent::Input(event) if app.emoji_picker.is_some() => {
+                self.handle_emoji_picker(app, event.code);
+            }
+            InputEvent::Input(event) if app.pending_delete.is_some() => {
+                match event.code {
+                    KeyCode::Char('y') | KeyCode::Enter => app.confirm_delete(),
+                    _ => app.cancel_delete(),
                 }
-                _ => {
-                    match app.active_block {
-                        ActiveBlock::Channels => {
-                            self.update_list_state(&mut app.selected_channel_index, 
-                                &app.channel_list, event.code)
-                                .expect("Update channel list state expect");
-                            self.select_list_element(app, 
-                                app.selected_channel_index, 
-                                app.channel_list.iter()
-                                    .map(|c| (c.id.clone(), c.name.clone()))
-                                    .collect(), 
-                                event.code);
-                        },
-                        ActiveBlock::Users => {
-                            self.update_list_state(&mut app.selected_user_index,
-                                &app.user_list, event.code)
-                                .expect("Update user list state expect");
-                            self.select_list_element(app, 
-                                app.selected_user_index, 
-                                app.user_list.iter()
-                                    .map(|u| (u.id.clone(), u.name.clone()))
-                                    .collect(),
-                                event.code);
-                        },
-                        ActiveBlock::Teams => {
-
-                        }
-                        ActiveBlock::Input => {
-                            self.handle_user_intput(app, event.code);
-                        }
-                        ActiveBlock::None => {
-                            // If no window is focused, check if user pressed 'c' to select channel
-                            // if event.code == KeyCode::Char('c') {
-                            //     app.active_block.clone_from(&ActiveBlock::Channels);
-                            // } else if event.code == KeyCode::Char('u') {
-                            //     app.active_block.clone_from(&ActiveBlock::Users);
-                            // } else if event.code == KeyCode::Char('t') {
-                            //     app.active_block.clone_from(&ActiveBlock::Teams);
-                            // }
-
-                            // No active block, navigate hovered block
-                            self.navigate_windows(event.code, app);
-                        }
-                        _ => {
-                            
+            }
+            InputEvent::Input(event) => {
+                let action = app.keymap.get_action(Context::from(app.active_block), event);
+                match action {
+                    // Priority key presses
+                    Some(Action::Quit) => {
+                        return Ok(InputEvent::Quit);
+                    }
+                    // Deselect focused window
+                    Some(Action::Deselect) => {
+                        // TODO: Move this to a function
+                        app.active_block.clone_from(&ActiveBlock::None);
+                        app.clear_error();
+                        app.cancel_edit();
+                        // TODO: Improve to only use one selected index
+                        // app.selected_team_index = None;
+                        // app.selected_channel_index = None;
+                        // app.selected_user_index = None;
+                    }
+                    Some(Action::FocusTeams) => self.focus(app, ActiveBlock::Teams),
+                    Some(Action::FocusChannels) => self.focus(app, ActiveBlock::Channels),
+                    Some(Action::FocusUsers) => self.focus(app, ActiveBlock::Users),
+                    Some(Action::FocusMessages) => self.focus(app, ActiveBlock::Messages),
+                    Some(Action::FocusInput) => self.focus(app, ActiveBlock::Input),
+                    _ => {
+                        match app.active_block {
+                            ActiveBlock::Channels => {
+                                self.update_list_state(&mut app.selected_channel_index, 
+                                    &app.channel_list, action)
+                                    .expect("Update channel list state expect");
+                                self.select_list_element(app, 
+                                    app.selected_channel_index, 
+                                    app.channel_list.iter()
+                                        .map(|c| (c.id.clone(), app.get_conversation_name(c)))
+                                        .collect(), 
+                                    action);
+                            },
+                            ActiveBlock::Users => {
+                                self.update_list_state(&mut app.selected_user_index,
+                                    &app.user_list, action)
+                                    .expect("Update user list state expect");
+                                self.select_user(app, action);
+                            },
+                            ActiveBlock::Teams => {
+                                self.update_list_state(&mut app.selected_team_index,
+                                    &app.workspaces, action)
+                                    .expect("Update team list state expect");
+                                if let (Some(Action::Select), Some(index)) = (action, app.selected_team_index) {
+                                    app.switch_workspace(index);
+                                }
+                            }
+                            ActiveBlock::Input => {
+                                self.handle_user_intput(app, action, event);
+                            }
+                            ActiveBlock::Messages => {
+                                self.handle_messages(app, action);
+                            }
+                            ActiveBlock::Thread => {
+                                self.handle_thread(app, action);
+                            }
+                            ActiveBlock::None => {
+                                // No active block, navigate hovered block
+                                self.navigate_windows(action, app);
+                            }
+                            _ => {
+                                
+                            }
                         }
                     }
                 }
-
             },
+            InputEvent::Api(workspace, response) => {
+                app.with_workspace(workspace, |app| app.handle_api_response(response));
+            }
+            InputEvent::Realtime(workspace, event) => {
+                app.with_workspace(workspace, |app| app.handle_realtime_event(event));
+            }
+            InputEvent::Tick => {
+                app.on_tick();
+            }
             _ => {},
         }
         Ok(InputEvent::Tick)
     }
 
-    fn handle_user_intput(&self, app: &mut App, code: KeyCode){
-        match code {
-            KeyCode::Left => {
+    fn handle_user_intput(&self, app: &mut App, action: Option<Action>, event: KeyEvent){
+        match action {
+            Some(Action::Left) => {
                 if app.input_idx > 0 {
                     let c = app.input[app.input_idx - 1];
                     app.input_idx -= 1;
-                    app.input_cursor_position -= util::calculate_character_width(c);
+                    app.input_cursor_position -= util::calculate_input_width(c);
                 }
             }
-            KeyCode::Right => {
+            Some(Action::Right) => {
                 if app.input_idx < app.input.len() {
                     let c = app.input[app.input_idx];
                     app.input_idx += 1;
-                    app.input_cursor_position += util::calculate_character_width(c);
+                    app.input_cursor_position += util::calculate_input_width(c);
                 }
             }
-            KeyCode::Backspace => {
+            Some(Action::LineStart) => {
+                app.input_idx = 0;
+                app.input_cursor_position = 0;
+            }
+            Some(Action::LineEnd) => {
+                app.input_idx = app.input.len();
+                app.input_cursor_position = app.input.iter()
+                    .map(|c| util::calculate_input_width(*c))
+                    .sum();
+            }
+            Some(Action::Backspace) => {
                 if !app.input.is_empty() && app.input_idx > 0 {
                     let c = app.input.remove(app.input_idx - 1);
                     app.input_idx -= 1;
-                    app.input_cursor_position -= util::calculate_character_width(c);
+                    app.input_cursor_position -= util::calculate_input_width(c);
                   }
             }
-            KeyCode::Char(c) => {
-                app.input.insert(app.input_idx, c);
+            Some(Action::NewLine) => {
+                app.input.insert(app.input_idx, '\n');
                 app.input_idx += 1;
-                app.input_cursor_position += util::calculate_character_width(c);
+                app.input_cursor_position += util::calculate_input_width('\n');
             }
-            KeyCode::Enter => {
+            Some(Action::SendMessage) => {
                 // Send message
-                if let Some(channel_index) = app.selected_channel_index {
-                    messages_interface::send_channel_message(
-                        &String::from_iter(&app.input),
-                        &app.channel_list[channel_index].name,
-                        &app.oauth_token).expect("Send channel message expect");
-
-                    app.input.clear();
-                    app.input_idx = 0;
-                    app.input_cursor_position = 0;
+                app.send_message();
+            }
+            Some(Action::ToggleReplyTarget) => {
+                // Switch between the channel and the open thread
+                app.toggle_reply_target();
+            }
+            Some(_) => {}
+            // Keys that are not bound type, shift is part of the character
+            None => {
+                if let KeyCode::Char(c) = event.code {
+                    if (event.modifiers - KeyModifiers::SHIFT).is_empty() {
+                        app.input.insert(app.input_idx, c);
+                        app.input_idx += 1;
+                        app.input_cursor_position += util::calculate_input_width(c);
+                    }
                 }
             }
+        }
+    }
+
+    fn handle_messages(&self, app: &mut App, action: Option<Action>){
+        let Some(action) = action else {
+            return;
+        };
+        match action {
+            Action::Up => {
+                app.select_older_message(1);
+            }
+            Action::Down => {
+                app.select_newer_message(1);
+            }
+            Action::PageUp => {
+                app.select_older_message(MESSAGES_PAGE_SIZE);
+            }
+            Action::PageDown => {
+                app.select_newer_message(MESSAGES_PAGE_SIZE);
+            }
+            Action::Top => {
+                app.select_oldest_message();
+            }
+            Action::Bottom => {
+                app.select_newest_message();
+            }
+            Action::Select => {
+                app.open_thread();
+            }
+            Action::AddReaction => {
+                app.open_emoji_picker(PickerMode::Add);
+            }
+            Action::RemoveReaction => {
+                app.open_emoji_picker(PickerMode::Remove);
+            }
+            Action::EditMessage => {
+                app.start_edit();
+            }
+            Action::DeleteMessage => {
+                app.start_delete();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_emoji_picker(&self, app: &mut App, code: KeyCode){
+        let Some(picker) = app.emoji_picker.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Esc => app.close_emoji_picker(),
+            KeyCode::Enter => app.pick_emoji(),
+            KeyCode::Up => picker.select_previous(),
+            KeyCode::Down => picker.select_next(),
+            KeyCode::Backspace => picker.pop_char(),
+            KeyCode::Char(c) => picker.push_char(c),
+            _ => {}
+        }
+    }
+
+    fn handle_thread(&self, app: &mut App, action: Option<Action>){
+        let Some(action) = action else {
+            return;
+        };
+        match action {
+            Action::Up => {
+                app.thread_scroll = app.thread_scroll.saturating_sub(1);
+            }
+            // The thread pane stops at its last wrapped line when it is drawn
+            Action::Down => {
+                app.thread_scroll = app.thread_scroll.saturating_add(1);
+            }
+            Action::PageUp => {
+                app.thread_scroll = app.thread_scroll.saturating_sub(MESSAGES_PAGE_SIZE as u16);
+            }
+            Action::PageDown => {
+                app.thread_scroll = app.thread_scroll.saturating_add(MESSAGES_PAGE_SIZE as u16);
+            }
+            Action::Top => {
+                app.thread_scroll = 0;
+            }
+            Action::Bottom => {
+                app.thread_scroll = u16::MAX;
+            }
+            Action::Select => {
+                app.start_thread_reply();
+            }
+            Action::CloseThread => {
+                app.close_thread();
+            }
             _ => {}
         }
     }
 
     fn update_list_state<T>(&self, list_index: &mut Option<usize>,
-        list: &Vec<T>, code: KeyCode)
+        list: &[T], action: Option<Action>)
     -> Result<(), Box<dyn std::error::Error>>{ 
-        if list.len() == 0 {
+        if list.is_empty() {
             return Ok(());
         }
 
-        match code {
-            KeyCode::Up => {
+        match action {
+            Some(Action::Up) => {
                 if let Some(list_index) = list_index {
                     if *list_index > 0 {
                         *list_index -= 1;
@@ -162,7 +287,7 @@ impl<'a> InputReciever<'a> {
                     *list_index = Some(0);
                 }
             }
-            KeyCode::Down => {
+            Some(Action::Down) => {
                 if let Some(list_index) = list_index {
                     if *list_index < list.len() - 1 {
                         *list_index += 1;
@@ -177,8 +302,8 @@ impl<'a> InputReciever<'a> {
         Ok(())
     }
 
-    fn select_list_element(&self, app: &mut App, list_index: Option<usize>, list: Vec<(String, String)>, code: KeyCode){
-        if code == KeyCode::Enter {
+    fn select_list_element(&self, app: &mut App, list_index: Option<usize>, list: Vec<(String, String)>, action: Option<Action>){
+        if action == Some(Action::Select) {
             if let Some(index) = list_index {
                 if index >= list.len() { return; }
 
@@ -190,26 +315,46 @@ impl<'a> InputReciever<'a> {
         }
     }
     
-    fn navigate_windows(&self, code: KeyCode, app: &mut App){
-        match code{
-            KeyCode::Up => {
+    // Open the direct message conversation with the selected user
+    fn select_user(&self, app: &mut App, action: Option<Action>){
+        if action != Some(Action::Select) {
+            return;
+        }
+
+        let selected_user = app.selected_user_index
+            .and_then(|index| app.user_list.get(index))
+            .map(|user| (user.id.clone(), user.get_display_name().to_owned()));
+        if let Some((user_id, user_name)) = selected_user {
+            app.open_direct_message(&user_id, &user_name);
+        }
+    }
+
+    fn navigate_windows(&self, action: Option<Action>, app: &mut App){
+        match action{
+            Some(Action::Up) => {
                 self.move_up(&mut app.hovered_block);
             }
-            KeyCode::Down => {
+            Some(Action::Down) => {
                 self.move_down(&mut app.hovered_block);
             }
-            KeyCode::Left => {
+            Some(Action::Left) => {
                 self.move_left(&mut app.hovered_block);
             }
-            KeyCode::Right => {
-                self.move_right(&mut app.hovered_block);
+            Some(Action::Right) => {
+                self.move_right(&mut app.hovered_block, app.thread_ts.is_some());
             }
-            KeyCode::Enter => {
+            Some(Action::Select) => {
                 app.active_block.clone_from(&app.hovered_block);
             }
             _ => {}
         }
     }
+
+    // Activate a block directly, it is also hovered once it is deselected
+    fn focus(&self, app: &mut App, block: ActiveBlock){
+        app.active_block.clone_from(&block);
+        app.hovered_block.clone_from(&block);
+    }
     
     fn move_up(&self, active_window_item: &mut ActiveBlock) {
         match active_window_item {
@@ -220,14 +365,22 @@ impl<'a> InputReciever<'a> {
                 *active_window_item = ActiveBlock::Channels;
             }
             ActiveBlock::Input => {
-                *active_window_item = ActiveBlock::Channels;
+                *active_window_item = ActiveBlock::Messages;
             }
             _ => {}
         }
     }
     
-    fn move_right(&self, active_window_item: &mut ActiveBlock) {
-        *active_window_item = ActiveBlock::Input;
+    fn move_right(&self, active_window_item: &mut ActiveBlock, has_thread: bool) {
+        match active_window_item {
+            ActiveBlock::Messages | ActiveBlock::Input if has_thread => {
+                *active_window_item = ActiveBlock::Thread;
+            }
+            ActiveBlock::Thread => {}
+            _ => {
+                *active_window_item = ActiveBlock::Input;
+            }
+        }
     }
     
     fn move_down(&self, active_window_item: &mut ActiveBlock) {
@@ -238,6 +391,9 @@ impl<'a> InputReciever<'a> {
             ActiveBlock::Channels => {
                 *active_window_item = ActiveBlock::Users;
             }
+            ActiveBlock::Messages | ActiveBlock::Thread => {
+                *active_window_item = ActiveBlock::Input;
+            }
             _ => {}
         }
     }
@@ -247,6 +403,12 @@ impl<'a> InputReciever<'a> {
             ActiveBlock::Input => {
                 *active_window_item = ActiveBlock::Users;
             }
+            ActiveBlock::Messages => {
+                *active_window_item = ActiveBlock::Channels;
+            }
+            ActiveBlock::Thread => {
+                *active_window_item = ActiveBlock::Messages;
+            }
             _ => {}
         }
     }
@@ -0,0 +1,497 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum KeymapError {
+    #[error("'{key}' in keymap.{context} is not a key: {message}")]
+    InvalidKey { context: Context, key: String, message: String },
+    #[error("'{key}' in keymap.{context} is bound to both {first} and {second}")]
+    Conflict { context: Context, key: String, first: Action, second: Action },
+    #[error("'{key}' in keymap.{context} replaces {first} of the preset, which has no other key there")]
+    Replaced { context: Context, key: String, first: Action, second: Action },
+    #[error("'{key}' in keymap.global is {action}, but {by} in {context} where {action} has no other key")]
+    Shadowed { context: Context, key: String, action: Action, by: Action },
+    #[error("'{key}' can not be bound in keymap.input, it is needed to type")]
+    TypingKey { key: String },
+    #[error("{action} does nothing in keymap.{context}, '{key}' is bound to it")]
+    WrongContext { context: Context, key: String, action: Action },
+    #[error("No key quits, bind one to quit in keymap.global")]
+    NoQuit,
+}
+
+// Where a binding applies. Global bindings apply in every context unless the context binds
+// the same key, except that keys for typing are never taken from the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Context {
+    // Also used when no block is active, to move between blocks
+    Global,
+    // Teams, channels and users
+    Sidebar,
+    // Messages and the open thread
+    Messages,
+    Input,
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Context::Global => "global",
+            Context::Sidebar => "sidebar",
+            Context::Messages => "messages",
+            Context::Input => "input",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    // Unbinds a key
+    None,
+    Quit,
+    Deselect,
+    FocusTeams,
+    FocusChannels,
+    FocusUsers,
+    FocusMessages,
+    FocusInput,
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    AddReaction,
+    RemoveReaction,
+    EditMessage,
+    DeleteMessage,
+    CloseThread,
+    Backspace,
+    LineStart,
+    LineEnd,
+    NewLine,
+    SendMessage,
+    ToggleReplyTarget,
+}
+
+impl Action {
+    // Whether the action does something when bound in `context`. Any action can be bound in
+    // global, it is done by whichever context is active
+    fn applies_to(&self, context: Context) -> bool {
+        use Action::*;
+        match self {
+            None | Quit | Deselect | FocusTeams | FocusChannels | FocusUsers | FocusMessages | FocusInput => true,
+            Up | Down | Select => context != Context::Input,
+            Left | Right => matches!(context, Context::Global | Context::Input),
+            PageUp | PageDown | Top | Bottom | AddReaction | RemoveReaction | EditMessage | DeleteMessage
+                | CloseThread => matches!(context, Context::Global | Context::Messages),
+            Backspace | LineStart | LineEnd | NewLine | SendMessage | ToggleReplyTarget =>
+                matches!(context, Context::Global | Context::Input),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The snake_case name used in the config
+        let name = format!("{:?}", self).chars().fold(String::new(), |mut name, c| {
+            if c.is_uppercase() && !name.is_empty() {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+            name
+        });
+        write!(f, "{}", name)
+    }
+}
+
+// A key with its modifiers, written like "q", "G", "ctrl-c", "alt-v" or "pgdn"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("esc", KeyCode::Esc),
+    ("enter", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pgup", KeyCode::PageUp),
+    ("pgdn", KeyCode::PageDown),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("space", KeyCode::Char(' ')),
+];
+
+// Other spellings of the names above
+const KEY_ALIASES: &[(&str, &str)] = &[
+    ("escape", "esc"),
+    ("return", "enter"),
+    ("del", "delete"),
+    ("ins", "insert"),
+    ("pageup", "pgup"),
+    ("pagedown", "pgdn"),
+];
+
+impl KeyChord {
+    pub fn parse(text: &str) -> Result<KeyChord, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        // The key itself can be '-', so only a non empty rest is taken as a modifier prefix
+        while let Some((prefix, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match prefix.to_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "meta" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier '{}'", prefix)),
+            };
+            rest = key;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            (None, _) => return Err("no key".to_string()),
+            _ => {
+                let name = rest.to_lowercase();
+                let name = KEY_ALIASES.iter()
+                    .find(|(alias, _)| *alias == name)
+                    .map(|(_, name)| name.to_string())
+                    .unwrap_or(name);
+                let function_key = name.strip_prefix('f')
+                    .and_then(|number| number.parse::<u8>().ok())
+                    .filter(|number| (1..=12).contains(number));
+                match (KEY_NAMES.iter().find(|(key_name, _)| *key_name == name), function_key) {
+                    (Some((_, code)), _) => *code,
+                    (None, Some(number)) => KeyCode::F(number),
+                    (None, None) => return Err(format!("unknown key '{}'", rest)),
+                }
+            }
+        };
+        Ok(KeyChord::new(code, modifiers))
+    }
+
+    // Terminals send shift with upper case letters and ctrl with lower case ones, so shift
+    // is kept in the character and control letters are lower case
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> KeyChord {
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => KeyCode::Char(c.to_ascii_lowercase()),
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::Char(c.to_ascii_uppercase()),
+            code => code,
+        };
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        KeyChord { code, modifiers }
+    }
+
+    // A key that types a character in the input
+    fn is_typing(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_)) && self.modifiers.is_empty()
+    }
+}
+
+impl From<KeyEvent> for KeyChord {
+    fn from(event: KeyEvent) -> Self {
+        KeyChord::new(event.code, event.modifiers)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "shift-")?;
+        }
+        match (self.code, KEY_NAMES.iter().find(|(_, code)| *code == self.code)) {
+            (_, Some((name, _))) => write!(f, "{}", name),
+            (KeyCode::Char(c), None) => write!(f, "{}", c),
+            (KeyCode::F(number), None) => write!(f, "f{}", number),
+            (code, None) => write!(f, "{:?}", code),
+        }
+    }
+}
+
+// "keymap.preset" in the config
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    #[default]
+    Default,
+    Vim,
+    Emacs,
+}
+
+type Bindings = &'static [(Context, &'static str, Action)];
+
+// Arrows, enter and esc
+const DEFAULT_BINDINGS: Bindings = &[
+    (Context::Global, "q", Action::Quit),
+    (Context::Global, "ctrl-c", Action::Quit),
+    (Context::Global, "esc", Action::Deselect),
+    (Context::Global, "t", Action::FocusTeams),
+    (Context::Global, "c", Action::FocusChannels),
+    (Context::Global, "u", Action::FocusUsers),
+    (Context::Global, "up", Action::Up),
+    (Context::Global, "down", Action::Down),
+    (Context::Global, "left", Action::Left),
+    (Context::Global, "right", Action::Right),
+    (Context::Global, "enter", Action::Select),
+    (Context::Messages, "k", Action::Up),
+    (Context::Messages, "j", Action::Down),
+    (Context::Messages, "pgup", Action::PageUp),
+    (Context::Messages, "pgdn", Action::PageDown),
+    (Context::Messages, "g", Action::Top),
+    (Context::Messages, "G", Action::Bottom),
+    (Context::Messages, "+", Action::AddReaction),
+    (Context::Messages, "-", Action::RemoveReaction),
+    (Context::Messages, "e", Action::EditMessage),
+    (Context::Messages, "d", Action::DeleteMessage),
+    (Context::Messages, "x", Action::CloseThread),
+    (Context::Input, "backspace", Action::Backspace),
+    (Context::Input, "home", Action::LineStart),
+    (Context::Input, "end", Action::LineEnd),
+    (Context::Input, "enter", Action::SendMessage),
+    (Context::Input, "alt-enter", Action::NewLine),
+    (Context::Input, "tab", Action::ToggleReplyTarget),
+];
+
+// hjkl everywhere outside the input, i to write
+const VIM_BINDINGS: Bindings = &[
+    (Context::Global, "h", Action::Left),
+    (Context::Global, "j", Action::Down),
+    (Context::Global, "k", Action::Up),
+    (Context::Global, "l", Action::Right),
+    (Context::Global, "i", Action::FocusInput),
+    (Context::Global, "m", Action::FocusMessages),
+    (Context::Sidebar, "l", Action::Select),
+    (Context::Messages, "ctrl-u", Action::PageUp),
+    (Context::Messages, "ctrl-d", Action::PageDown),
+    (Context::Messages, "ctrl-b", Action::PageUp),
+    (Context::Messages, "ctrl-f", Action::PageDown),
+    (Context::Input, "ctrl-h", Action::Backspace),
+];
+
+// Control and alt keys, so no letter is taken
+const EMACS_BINDINGS: Bindings = &[
+    (Context::Global, "q", Action::None),
+    (Context::Global, "t", Action::None),
+    (Context::Global, "c", Action::None),
+    (Context::Global, "u", Action::None),
+    (Context::Global, "ctrl-g", Action::Deselect),
+    (Context::Global, "alt-t", Action::FocusTeams),
+    (Context::Global, "alt-c", Action::FocusChannels),
+    (Context::Global, "alt-u", Action::FocusUsers),
+    (Context::Global, "alt-m", Action::FocusMessages),
+    (Context::Global, "alt-i", Action::FocusInput),
+    (Context::Global, "ctrl-p", Action::Up),
+    (Context::Global, "ctrl-n", Action::Down),
+    (Context::Global, "ctrl-b", Action::Left),
+    (Context::Global, "ctrl-f", Action::Right),
+    (Context::Messages, "alt-v", Action::PageUp),
+    (Context::Messages, "ctrl-v", Action::PageDown),
+    (Context::Messages, "alt-<", Action::Top),
+    (Context::Messages, "alt->", Action::Bottom),
+    (Context::Input, "ctrl-a", Action::LineStart),
+    (Context::Input, "ctrl-e", Action::LineEnd),
+    (Context::Input, "ctrl-h", Action::Backspace),
+];
+
+impl Preset {
+    // The presets change the default bindings
+    fn get_bindings(&self) -> Bindings {
+        match self {
+            Preset::Default => &[],
+            Preset::Vim => VIM_BINDINGS,
+            Preset::Emacs => EMACS_BINDINGS,
+        }
+    }
+}
+
+// "keymap" in the config: a preset and bindings from key to action that replace its keys
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct KeymapConfig {
+    pub preset: Preset,
+    pub global: BTreeMap<String, Action>,
+    pub sidebar: BTreeMap<String, Action>,
+    pub messages: BTreeMap<String, Action>,
+    pub input: BTreeMap<String, Action>,
+}
+
+impl KeymapConfig {
+    // Build the keymap, keys that can not work together are an error
+    pub fn build(&self) -> Result<Keymap, KeymapError> {
+        let mut keymap = Keymap { bindings: HashMap::new() };
+        for (context, key, action) in DEFAULT_BINDINGS.iter().chain(self.preset.get_bindings()) {
+            let chord = KeyChord::parse(key).expect("Parse preset key expect");
+            keymap.bind(*context, chord, *action);
+        }
+        let preset = keymap.clone();
+
+        let contexts = [
+            (Context::Global, &self.global),
+            (Context::Sidebar, &self.sidebar),
+            (Context::Messages, &self.messages),
+            (Context::Input, &self.input),
+        ];
+        let mut user_bindings = Vec::new();
+        for (context, bindings) in contexts {
+            // Two ways of writing the same key, like "ctrl-c" and "C-c"
+            let mut seen: HashMap<KeyChord, Action> = HashMap::new();
+            for (key, action) in bindings {
+                let chord = KeyChord::parse(key).map_err(|message| KeymapError::InvalidKey {
+                    context,
+                    key: key.to_string(),
+                    message,
+                })?;
+                match seen.insert(chord, *action) {
+                    Some(first) if first != *action => {
+                        return Err(KeymapError::Conflict { context, key: chord.to_string(), first, second: *action });
+                    }
+                    _ => {}
+                }
+                if context == Context::Input && chord.is_typing() {
+                    return Err(KeymapError::TypingKey { key: key.to_string() });
+                }
+                if !action.applies_to(context) {
+                    return Err(KeymapError::WrongContext { context, key: key.to_string(), action: *action });
+                }
+                keymap.bind(context, chord, *action);
+                user_bindings.push((context, chord, *action));
+            }
+        }
+
+        // Taking over a key is fine as long as what it did before, or what a global key does,
+        // can still be reached with another key
+        for (context, chord, action) in user_bindings {
+            if action == Action::None {
+                continue;
+            }
+            if let Some(first) = preset.lookup(context, chord) {
+                if first != action && keymap.get_key_name(context, first).is_none() {
+                    return Err(KeymapError::Replaced { context, key: chord.to_string(), first, second: action });
+                }
+            }
+            if context != Context::Global {
+                continue;
+            }
+            for other in [Context::Sidebar, Context::Messages, Context::Input] {
+                if !action.applies_to(other) || (other == Context::Input && chord.is_typing()) {
+                    continue;
+                }
+                match keymap.lookup(other, chord) {
+                    Some(by) if by != action && keymap.get_key_name(other, action).is_none() => {
+                        return Err(KeymapError::Shadowed { context: other, key: chord.to_string(), action, by });
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if !keymap.bindings.values().any(|action| *action == Action::Quit) {
+            return Err(KeymapError::NoQuit);
+        }
+        Ok(keymap)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<(Context, KeyChord), Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        KeymapConfig::default().build().expect("Build default keymap expect")
+    }
+}
+
+impl Keymap {
+    // An unbound global key is removed, in other contexts it hides the global binding
+    fn bind(&mut self, context: Context, chord: KeyChord, action: Action) {
+        if context == Context::Global && action == Action::None {
+            self.bindings.remove(&(context, chord));
+        }
+        else{
+            self.bindings.insert((context, chord), action);
+        }
+    }
+
+    // The action of a key press in `context`
+    pub fn get_action(&self, context: Context, event: KeyEvent) -> Option<Action> {
+        self.lookup(context, KeyChord::from(event))
+    }
+
+    fn lookup(&self, context: Context, chord: KeyChord) -> Option<Action> {
+        let action = match self.bindings.get(&(context, chord)) {
+            Some(action) => Some(*action),
+            None if context == Context::Input && chord.is_typing() => None,
+            None => self.bindings.get(&(Context::Global, chord)).copied(),
+        };
+        action.filter(|action| *action != Action::None)
+    }
+
+    // A key that does `action` in `context` to show in hints, the shortest one if there
+    // are several
+    pub fn get_key_name(&self, context: Context, action: Action) -> Option<String> {
+        self.bindings.keys()
+            .map(|(_, chord)| *chord)
+            .filter(|chord| self.lookup(context, *chord) == Some(action))
+            .map(|chord| chord.to_string())
+            .min_by_key(|name| (name.len(), name.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(json: &str) -> Result<Keymap, KeymapError> {
+        serde_json::from_str::<KeymapConfig>(json).expect("Parse keymap expect").build()
+    }
+
+    #[test]
+    fn reports_lost_preset_and_global_keys() {
+        assert!(build(r#"{ "preset": "vim", "global": { "ctrl-q": "quit", "q": "none" } }"#).is_ok());
+        // Up still has k in the messages
+        assert!(build(r#"{ "messages": { "up": "add_reaction" } }"#).is_ok());
+        // Close thread does nothing in the sidebar
+        assert!(build(r#"{ "preset": "vim", "global": { "j": "close_thread" } }"#).is_ok());
+
+        assert!(matches!(build(r#"{ "messages": { "e": "add_reaction" } }"#),
+            Err(KeymapError::Replaced { context: Context::Messages, first: Action::EditMessage, .. })));
+        assert!(matches!(build(r#"{ "global": { "d": "focus_input" } }"#),
+            Err(KeymapError::Shadowed { context: Context::Messages, by: Action::DeleteMessage, .. })));
+        assert!(build(r#"{ "messages": { "d": "none" }, "global": { "d": "focus_input" } }"#).is_ok());
+    }
+
+    #[test]
+    fn names_the_quit_key_of_the_preset() {
+        assert_eq!(build("{}").expect("Build expect").get_key_name(Context::Global, Action::Quit).as_deref(), Some("q"));
+        assert_eq!(build(r#"{ "preset": "emacs" }"#).expect("Build expect")
+            .get_key_name(Context::Global, Action::Quit).as_deref(), Some("ctrl-c"));
+    }
+}
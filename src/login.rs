@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::config::{self, Config, ConfigError};
+use crate::credentials::{CredentialError, Credentials};
+use crate::slack_interface::{self, auth_interface, SlackError};
+
+// User scopes for everything the client does
+const USER_SCOPES: &[&str] = &[
+    "channels:history", "channels:read", "channels:write",
+    "groups:history", "groups:read", "groups:write",
+    "im:history", "im:read", "im:write",
+    "mpim:history", "mpim:read", "mpim:write",
+    "chat:write", "reactions:read", "reactions:write",
+    "users:read", "emoji:read", "team:read",
+];
+
+const REDIRECT_PATH: &str = "/callback";
+
+// Random bytes in the state
+const STATE_LENGTH: usize = 16;
+
+// How long to wait for the browser to come back
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(300);
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Error)]
+pub enum LoginError {
+    #[error("Set \"client_id\" and \"client_secret\" of your Slack app in the config, or SLACK_CLIENT_ID and SLACK_CLIENT_SECRET")]
+    MissingClient,
+    #[error("Could not listen for the redirect on port {port}: {error}")]
+    Listen { port: u16, error: io::Error },
+    #[error("Gave up waiting for the browser after {0} seconds")]
+    Timeout(u64),
+    #[error("Slack did not authorize the app: {0}")]
+    Denied(String),
+    #[error("The redirect does not belong to this login")]
+    StateMismatch,
+    #[error("Could not generate the login state: {0}")]
+    State(getrandom::Error),
+    #[error("Could not get a token: {0}")]
+    Slack(#[from] SlackError),
+    #[error("Workspace '{0}' gets its token from token_command, save the token there instead")]
+    TokenCommand(String),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Credentials(#[from] CredentialError),
+}
+
+// Sign in with the browser and save the token in the credentials file, as the workspace
+// called `name` or else after the Slack team
+pub fn login(config: &Config, name: Option<&str>) -> Result<(), LoginError> {
+    sign_in(config, name, open_browser)
+}
+
+// `login` with the authorize page opened by `open`
+fn sign_in(config: &Config, name: Option<&str>, open: impl FnOnce(&str)) -> Result<(), LoginError> {
+    let (Some(client_id), Some(client_secret)) = (&config.client_id, &config.client_secret) else {
+        return Err(LoginError::MissingClient);
+    };
+    if let Some(name) = name {
+        check_token_command(config, name)?;
+    }
+
+    let (listeners, port) = listen(config.redirect_port)?;
+    let redirect_uri = format!("http://localhost:{}{}", port, REDIRECT_PATH);
+    let state = get_state()?;
+
+    let authorize_url = format!("{}?client_id={}&user_scope={}&redirect_uri={}&state={}",
+        config.authorize_url,
+        slack_interface::encode(client_id),
+        slack_interface::encode(&USER_SCOPES.join(",")),
+        slack_interface::encode(&redirect_uri),
+        state);
+    println!("Opening {}", authorize_url);
+    println!("If no browser opens, visit the URL above to sign in");
+    open(&authorize_url);
+
+    let code = wait_for_code(&listeners, &state)?;
+    slack_interface::set_api_url(&config.api_url);
+    let access = auth_interface::exchange_code(client_id, client_secret, &code, &redirect_uri)?;
+
+    let name = name.unwrap_or(&access.team);
+    check_token_command(config, name)?;
+
+    // The app token of the workspace is kept
+    let store = config.open_credentials();
+    let app_token = store.get(name)?.and_then(|credentials| credentials.app_token);
+    store.set(name, Credentials { oauth_token: access.oauth_token, app_token })?;
+
+    let path = config.path.clone().unwrap_or_else(|| PathBuf::from("config.json"));
+    config::save_workspace(&path, name)?;
+    println!("Signed in as {} on {} ({}), saved as workspace '{}' in {} with its token in {}",
+        access.user_id, access.team, access.team_id, name, path.display(), store.get_path().display());
+    Ok(())
+}
+
+// The token of a workspace with a token_command can not be saved
+fn check_token_command(config: &Config, name: &str) -> Result<(), LoginError> {
+    let uses_command = config.get_entries().iter()
+        .any(|workspace| workspace.name == name && workspace.token_command.is_some());
+    match uses_command {
+        true => Err(LoginError::TokenCommand(name.to_string())),
+        false => Ok(()),
+    }
+}
+
+// localhost in the redirect can be either loopback address, so both are listened on. Returns
+// the listeners and their port, which is picked by the system when `port` is 0
+fn listen(port: u16) -> Result<(Vec<TcpListener>, u16), LoginError> {
+    let listen_error = |error| LoginError::Listen { port, error };
+    let ipv4 = TcpListener::bind(("127.0.0.1", port)).map_err(listen_error)?;
+    let port = ipv4.local_addr().map_err(listen_error)?.port();
+
+    let mut listeners = vec![ipv4];
+    // Hosts without IPv6 only have the first
+    if let Ok(ipv6) = TcpListener::bind(("::1", port)) {
+        listeners.push(ipv6);
+    }
+    Ok((listeners, port))
+}
+
+// A value the redirect has to return, so other sites can not log the user in
+fn get_state() -> Result<String, LoginError> {
+    let mut bytes = [0; STATE_LENGTH];
+    getrandom::getrandom(&mut bytes).map_err(LoginError::State)?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+// $BROWSER or the opener of the platform, the URL is also printed in case this fails
+fn open_browser(url: &str) {
+    let opener = std::env::var("BROWSER").ok()
+        .filter(|browser| !browser.is_empty())
+        .unwrap_or_else(|| match cfg!(target_os = "macos") {
+            true => "open".to_string(),
+            false => "xdg-open".to_string(),
+        });
+    let _ = Command::new(opener)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+// Wait for Slack to redirect the browser to one of the listeners, returns the code in the redirect
+fn wait_for_code(listeners: &[TcpListener], state: &str) -> Result<String, LoginError> {
+    for listener in listeners {
+        let port = listener.local_addr().map(|address| address.port()).unwrap_or_default();
+        listener.set_nonblocking(true).map_err(|error| LoginError::Listen { port, error })?;
+    }
+
+    let started = Instant::now();
+    loop {
+        let mut accepted = None;
+        for listener in listeners {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    accepted = Some(stream);
+                    break;
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {}
+                Err(error) => {
+                    let port = listener.local_addr().map(|address| address.port()).unwrap_or_default();
+                    return Err(LoginError::Listen { port, error });
+                }
+            }
+        }
+        let Some(mut stream) = accepted else {
+            if started.elapsed() >= LOGIN_TIMEOUT {
+                return Err(LoginError::Timeout(LOGIN_TIMEOUT.as_secs()));
+            }
+            thread::sleep(ACCEPT_INTERVAL);
+            continue;
+        };
+
+        // Browsers also ask for things like /favicon.ico
+        let Some(params) = read_redirect(&mut stream) else {
+            let _ = respond(&mut stream, "404 Not Found", "Not found");
+            continue;
+        };
+
+        let param = |name: &str| params.get(name).map(|value| value.as_str()).unwrap_or_default();
+        // Any local page can send the browser here, only a redirect with the state can end the login
+        if param("state") != state {
+            let _ = respond(&mut stream, "400 Bad Request", &LoginError::StateMismatch.to_string());
+            continue;
+        }
+        let result = if !param("error").is_empty() {
+            Err(LoginError::Denied(param("error").to_string()))
+        }
+        else if param("code").is_empty() {
+            Err(LoginError::Denied("no code in the redirect".to_string()))
+        }
+        else {
+            Ok(param("code").to_string())
+        };
+
+        let message = match &result {
+            Ok(_) => "Signed in, you can close this window and go back to slacker-tui".to_string(),
+            Err(error) => error.to_string(),
+        };
+        let _ = respond(&mut stream, "200 OK", &message);
+        return result;
+    }
+}
+
+// The query parameters of a request to the redirect path
+fn read_redirect(stream: &mut TcpStream) -> Option<HashMap<String, String>> {
+    stream.set_nonblocking(false).ok()?;
+    stream.set_read_timeout(Some(Duration::from_secs(5))).ok()?;
+
+    let mut request_line = String::new();
+    BufReader::new(&mut *stream).read_line(&mut request_line).ok()?;
+    let target = request_line.split_whitespace().nth(1)?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if path != REDIRECT_PATH {
+        return None;
+    }
+
+    let params = query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (slack_interface::decode(name), slack_interface::decode(value)))
+        .collect();
+    Some(params)
+}
+
+// The message can contain parameters of the redirect, so it is escaped
+fn respond(stream: &mut TcpStream, status: &str, message: &str) -> io::Result<()> {
+    let body = format!("<!DOCTYPE html><html><body><p>{}</p></body></html>", escape_html(message));
+    write!(stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl::easy::Easy;
+    use serde_json::{json, Value};
+    use std::fs;
+
+    // Get a page like a browser would, following redirects. Returns the status and the body
+    fn browse(url: &str) -> (u32, String) {
+        let mut handle = Easy::new();
+        handle.url(url).expect("Browse url expect");
+        handle.follow_location(true).expect("Follow redirects expect");
+        let mut body = Vec::new();
+        {
+            let mut transfer = handle.transfer();
+            transfer.write_function(|data| {
+                body.extend_from_slice(data);
+                Ok(data.len())
+            }).expect("Write function expect");
+            transfer.perform().expect("Browse expect");
+        }
+        (handle.response_code().expect("Response code expect"), String::from_utf8_lossy(&body).to_string())
+    }
+
+    #[test]
+    fn escapes_redirect_errors() {
+        let error = LoginError::Denied("<script>alert('x')</script>".to_string());
+        assert_eq!(escape_html(&error.to_string()),
+            "Slack did not authorize the app: &lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;");
+    }
+
+    #[test]
+    fn states_are_random() {
+        let state = get_state().expect("Get state expect");
+        assert_eq!(state.len(), STATE_LENGTH * 2);
+        assert!(state.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(state, get_state().expect("Get state expect"));
+    }
+
+    #[test]
+    fn only_the_login_state_ends_the_wait() {
+        let (listeners, port) = listen(0).expect("Listen expect");
+        let callback = format!("http://localhost:{}{}", port, REDIRECT_PATH);
+        let browser = thread::spawn(move || {
+            // Another page can not end the login with its own error
+            let forged = browse(&format!("{}?error=%3Cb%3Ehacked%3C%2Fb%3E&state=guess", callback));
+            let redirect = browse(&format!("{}?code=mock-code-1&state=right", callback));
+            (forged, redirect)
+        });
+
+        assert_eq!(wait_for_code(&listeners, "right").expect("Wait for code expect"), "mock-code-1");
+        let ((forged_status, forged_body), (status, _)) = browser.join().expect("Browser expect");
+        assert_eq!(forged_status, 400);
+        assert!(!forged_body.contains("hacked"));
+        assert_eq!(status, 200);
+    }
+
+    #[test]
+    fn signs_in_with_the_mock_server() {
+        let server = slack_interface::start_mock();
+        let directory = std::env::temp_dir().join(format!("slacker-tui-login-{}", std::process::id()));
+        fs::create_dir_all(&directory).expect("Create test directory expect");
+        let path = directory.join("config.json");
+        let text = json!({
+            "api_url": server.api_url,
+            "authorize_url": server.api_url.replace("/api", "/oauth/v2/authorize"),
+            "redirect_port": 0,
+            "client_id": "3651690811730.3651636987891",
+            "client_secret": "mock-secret",
+        });
+        fs::write(&path, text.to_string()).expect("Write test config expect");
+
+        let (config, _) = config::load_for_login(Some(&path)).expect("Load test config expect");
+        let mut browser = None;
+        sign_in(&config, Some("ghosts"), |url| {
+            let url = url.to_string();
+            browser = Some(thread::spawn(move || browse(&url)));
+        }).expect("Sign in expect");
+        let (status, body) = browser.expect("Browser opened expect").join().expect("Browser expect");
+        assert_eq!(status, 200);
+        assert!(body.contains("Signed in"));
+
+        // The token is in the credentials file and the workspace in the config
+        let credentials = config.open_credentials().get("ghosts").expect("Read credentials expect");
+        assert!(credentials.expect("Saved credentials expect").oauth_token.starts_with("xoxp-mock-"));
+        let saved: Value = serde_json::from_str(&fs::read_to_string(&path).expect("Read config expect"))
+            .expect("Parse config expect");
+        assert_eq!(saved["workspaces"][0]["name"], "ghosts");
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
@@ -5,62 +5,166 @@ use crossterm::{
 };
 // use rand::{distributions::Alphanumeric, prelude::*};
 use serde::{Deserialize, Serialize, de::Expected};
-use serde_json::Value;
-use std::fs;
+use std::env;
 use std::io;
+use std::path::PathBuf;
+use std::process;
 use std::sync::mpsc;
 use std::thread;
 use std::time::{Duration, Instant};
 
+mod emoji;
 mod home;
 mod messages;
+mod mrkdwn;
 mod input_reciever;
+mod keymap;
+mod login;
 mod slack_interface;
 mod ui;
 mod util;
 mod app;
+mod config;
+mod credentials;
+mod network;
+mod realtime;
+mod timestamp;
+mod workspace;
 
-use slack_interface::{user_interface, channel_interface, messages_interface};
+// The tests run against the mock Slack API of the examples
+#[cfg(test)]
+#[path = "../examples/mock_slack.rs"]
+#[allow(dead_code)]
+mod mock_slack;
+
+use slack_interface::auth_interface;
 
 // Input events
 pub enum InputEvent<T> { 
     Input(T),
+    // Events of a workspace, by its index in the config
+    Api(usize, network::ApiResponse),
+    Realtime(usize, slack_interface::realtime_interface::RealtimeEvent),
     Tick,
     Quit,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // For testing without setting terminal to raw mode
-    // let config = crate::parse_config().expect("Parse config expect");
-    // let oauth_token = config["oauth_token"].as_str().expect("OAuth token is not a string");
-    // let user_list = user_interface::get_user_list(oauth_token).expect("Get user list expect");
-    // let channel_list = channel_interface::get_channel_list(oauth_token).expect("Get channel list expect");
-    // let messages_list = messages_interface::get_channel_messages(&channel_list[0].id, oauth_token).expect("Get messages list expect");
+const USAGE: &str = "Usage: slacker-tui [--config PATH] [COMMAND]
+
+Commands:
+    config check        Check the config and sign in to every workspace with auth.test
+    login [WORKSPACE]   Sign in with the browser and save the token, as WORKSPACE or the Slack team name
 
-    // let send_message = messages_interface::send_channel_message(&channel_list[0].name, oauth_token).expect("Send message expect");
+Options:
+    --config PATH       Read the config from PATH instead of $XDG_CONFIG_HOME/slacker-tui/config.json";
 
-    // println!("Conversation : {}, {}", channel_list[0].name, channel_list[0].id);
-    // println!("Success");
-    // return Ok(());  
+enum Command {
+    Run,
+    ConfigCheck,
+    Login(Option<String>),
+    Help,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(Command, Option<PathBuf>), String> {
+    let mut config_path = None;
+    let mut words = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" | "-c" => match args.next() {
+                Some(path) => config_path = Some(PathBuf::from(path)),
+                None => return Err("--config needs a path".to_string()),
+            },
+            "--help" | "-h" => return Ok((Command::Help, config_path)),
+            option if option.starts_with('-') => return Err(format!("Unknown option '{}'", option)),
+            _ => words.push(arg),
+        }
+    }
 
+    let words: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+    let command = match words.as_slice() {
+        [] => Command::Run,
+        ["config", "check"] => Command::ConfigCheck,
+        ["login"] => Command::Login(None),
+        ["login", name] => Command::Login(Some(name.to_string())),
+        _ => return Err(format!("Unknown command '{}'", words.join(" "))),
+    };
+    Ok((command, config_path))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let (command, config_path) = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        // Logging in also works without a config, it creates one
+        Command::Login(name) => {
+            let result = config::load_for_login(config_path.as_deref())
+                .map_err(login::LoginError::from)
+                .and_then(|(config, _)| login::login(&config, name.as_deref()));
+            if let Err(error) = result {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // Config errors are reported before the terminal is taken over
+    let (config, warnings) = match config::load(config_path.as_deref()) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+    if let Command::ConfigCheck = command {
+        process::exit(check_config(&config, &warnings));
+    }
 
     // enable_raw_mode().expect("can run in raw mode");
     let (tx, rx) = mpsc::channel(); // Create a channel for sending and receiving events
     let tick_rate = Duration::from_millis(200); // Tick rate in milliseconds
 
     // Create a thread for handling input events
-    thread::spawn(move || input_listen(&tx, &tick_rate));
+    let input_tx = tx.clone();
+    thread::spawn(move || input_listen(&input_tx, &tick_rate));
 
     enable_raw_mode().expect("Enable raw mode expect");
-    app::start_ui(&rx).expect("Start ui expect");
+    app::start_ui(config, warnings, &rx, tx).expect("Start ui expect");
 
     Ok(())
 }
 
-fn parse_config() -> Result<Value, Box<dyn std::error::Error>> {
-    let config_file = fs::read_to_string("config.json")?;
-    let config: Value = serde_json::from_str(&config_file)?;
-    Ok(config)
+// Print the config warnings and check the token of every workspace, returns the exit code
+fn check_config(config: &config::Config, warnings: &[String]) -> i32 {
+    println!("Config: {}", config.get_path_name());
+    println!("Credentials: {}", config.open_credentials().get_path().display());
+    for warning in warnings {
+        println!("Warning: {}", warning);
+    }
+
+    slack_interface::set_api_url(&config.api_url);
+    let mut exit_code = 0;
+    for workspace in config.get_workspaces() {
+        match auth_interface::test_auth(&workspace.oauth_token) {
+            Ok(identity) => println!("{}: ok, signed in as {} ({}) on {} ({}, {})", workspace.name,
+                identity.user, identity.user_id, identity.team, identity.team_id, identity.url),
+            Err(error) => {
+                println!("{}: {}", workspace.name, error);
+                exit_code = 1;
+            }
+        }
+    }
+    exit_code
 }
 
 fn input_listen(tx: &mpsc::Sender<InputEvent<KeyEvent>>, tick_rate: &Duration) -> Result<(), io::Error> {
@@ -1,4 +1,5 @@
 // use chrono::prelude::*;
+use chrono::Local;
 use crossterm::{
     event::{self, Event as CEvent, KeyCode, KeyEvent},
     terminal::{disable_raw_mode, enable_raw_mode},
@@ -13,7 +14,8 @@ use tui::{
     Terminal,
 };
 
-use crate::slack_interface::{messages_interface::{self, Message}};
+use crate::slack_interface::messages_interface::Message;
+use crate::timestamp::{self, TimeFormat};
 
 pub struct Conversation{
     pub conversation_name: String,
@@ -57,7 +59,9 @@ pub fn render_messages<'a>(conversation_name: &str, messages: &Vec<Message>) ->
             Spans::from(vec![
                 Span::raw("["),
                 Span::styled(
-                    message.ts.clone(), // TODO: Format time
+                    timestamp::parse_ts(&message.ts)
+                        .map(|time| timestamp::format_time(&time, TimeFormat::TwentyFourHour, &Local::now()))
+                        .unwrap_or_else(|| message.ts.clone()),
                     Style::default()
                         .fg(Color::Yellow)
                         .add_modifier(Modifier::BOLD),
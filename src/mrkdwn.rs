@@ -0,0 +1,414 @@
+// Parser and renderer for Slack's mrkdwn message format
+// https://api.slack.com/reference/surfaces/formatting
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+use crate::slack_interface::{channel_interface::Channel, user_interface::UserDirectory};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Inline {
+    Text(String),
+    Bold(Vec<Inline>),
+    Italic(Vec<Inline>),
+    Strike(Vec<Inline>),
+    Code(String),
+    // <@U123> or <@U123|name>
+    UserMention { id: String, label: Option<String> },
+    // <#C123|name>
+    ChannelLink { id: String, label: Option<String> },
+    // <!here>, <!channel>, <!everyone>, <!subteam^S123|@team>
+    Special { name: String, label: Option<String> },
+    // <https://example.com|label>
+    Link { url: String, label: Option<String> },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Block {
+    Line(Vec<Inline>),
+    Quote(Vec<Inline>),
+    Code(Vec<String>),
+}
+
+// Names used to show mentions and channel links
+pub struct Names<'a> {
+    pub users: &'a UserDirectory,
+    pub channels: &'a [Channel],
+    // The signed in user, mentions of them stand out
+    pub user_id: Option<&'a str>,
+}
+
+const CODE_FENCE: &str = "```";
+
+pub fn parse(text: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+
+    // Every other part is inside a code block, an unclosed fence is kept as text
+    let mut parts: Vec<&str> = text.split(CODE_FENCE).collect();
+    let mut unclosed = None;
+    if parts.len().is_multiple_of(2) {
+        unclosed = parts.pop();
+    }
+
+    let last_index = parts.len() - 1;
+    for (index, part) in parts.iter().enumerate() {
+        if index % 2 == 1 {
+            let code = part.strip_prefix('\n').unwrap_or(part);
+            let code = code.strip_suffix('\n').unwrap_or(code);
+            blocks.push(Block::Code(code.lines().map(decode_entities).collect()));
+            continue;
+        }
+
+        // Line breaks next to a fence belong to the fence
+        let mut part: &str = part;
+        if index > 0 {
+            part = part.strip_prefix('\n').unwrap_or(part);
+        }
+        if index < last_index {
+            part = part.strip_suffix('\n').unwrap_or(part);
+            if part.is_empty() {
+                continue;
+            }
+        }
+        if index == last_index {
+            if let Some(unclosed) = unclosed {
+                blocks.extend(parse_lines(&format!("{}{}{}", part, CODE_FENCE, unclosed)));
+                continue;
+            }
+            if part.is_empty() && index > 0 {
+                continue;
+            }
+        }
+        blocks.extend(parse_lines(part));
+    }
+    blocks
+}
+
+fn parse_lines(text: &str) -> Vec<Block> {
+    text.split('\n').map(|line| {
+        // Slack escapes > as &gt; in message text
+        let quote = line.strip_prefix("&gt;").or_else(|| line.strip_prefix('>'));
+        match quote {
+            Some(quote) => Block::Quote(parse_inline(quote.strip_prefix(' ').unwrap_or(quote))),
+            None => Block::Line(parse_inline(line)),
+        }
+    }).collect()
+}
+
+pub fn parse_inline(text: &str) -> Vec<Inline> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut inlines = Vec::new();
+    let mut buffer = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let parsed = match chars[i] {
+            '<' => parse_angle(&chars, i),
+            '`' => find(&chars, i + 1, |c| c == '`')
+                .filter(|&end| end > i + 1)
+                .map(|end| {
+                    let code: String = chars[i + 1..end].iter().collect();
+                    (Inline::Code(decode_entities(&code)), end + 1)
+                }),
+            '*' | '_' | '~' => parse_emphasis(&chars, i),
+            _ => None,
+        };
+
+        match parsed {
+            Some((inline, next)) => {
+                if !buffer.is_empty() {
+                    inlines.push(Inline::Text(decode_entities(&buffer)));
+                    buffer.clear();
+                }
+                inlines.push(inline);
+                i = next;
+            }
+            None => {
+                buffer.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+
+    if !buffer.is_empty() {
+        inlines.push(Inline::Text(decode_entities(&buffer)));
+    }
+    inlines
+}
+
+fn find(chars: &[char], start: usize, predicate: impl Fn(char) -> bool) -> Option<usize> {
+    (start..chars.len()).find(|&i| predicate(chars[i]))
+}
+
+// Parse <...>, returns the inline and the index after the closing >
+fn parse_angle(chars: &[char], start: usize) -> Option<(Inline, usize)> {
+    let end = find(chars, start + 1, |c| c == '>' || c == '<')?;
+    if chars[end] != '>' || end == start + 1 || chars[start + 1].is_whitespace() {
+        return None;
+    }
+
+    let content: String = chars[start + 1..end].iter().collect();
+    let (target, label) = match content.split_once('|') {
+        Some((target, label)) => (target, Some(decode_entities(label))),
+        None => (content.as_str(), None),
+    };
+
+    let inline = if let Some(id) = target.strip_prefix('@') {
+        Inline::UserMention { id: id.to_string(), label }
+    } else if let Some(id) = target.strip_prefix('#') {
+        Inline::ChannelLink { id: id.to_string(), label }
+    } else if let Some(name) = target.strip_prefix('!') {
+        Inline::Special { name: name.to_string(), label }
+    } else {
+        Inline::Link { url: decode_entities(target), label }
+    };
+    Some((inline, end + 1))
+}
+
+// Parse *bold*, _italic_ and ~strike~. Delimiters only count at word boundaries so
+// snake_case and 2*3*4 stay text
+fn parse_emphasis(chars: &[char], start: usize) -> Option<(Inline, usize)> {
+    let delimiter = chars[start];
+    let is_boundary = |c: Option<&char>| c.is_none_or(|c| !c.is_alphanumeric());
+
+    let previous = start.checked_sub(1).and_then(|i| chars.get(i));
+    let next = chars.get(start + 1);
+    if !is_boundary(previous) || next.is_none_or(|c| c.is_whitespace() || *c == delimiter) {
+        return None;
+    }
+
+    let end = (start + 2..chars.len()).find(|&i| chars[i] == delimiter
+        && !chars[i - 1].is_whitespace()
+        && is_boundary(chars.get(i + 1)))?;
+
+    let inner: String = chars[start + 1..end].iter().collect();
+    let children = parse_inline(&inner);
+    let inline = match delimiter {
+        '*' => Inline::Bold(children),
+        '_' => Inline::Italic(children),
+        _ => Inline::Strike(children),
+    };
+    Some((inline, end + 1))
+}
+
+// Slack escapes &, < and > in message text
+pub fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+// Render blocks into styled lines, code blocks get one line per line of code
+pub fn render(blocks: &[Block], names: &Names<'_>) -> Vec<Spans<'static>> {
+    let mut lines = Vec::new();
+    for block in blocks {
+        match block {
+            Block::Line(inlines) => {
+                lines.push(Spans::from(render_inlines(inlines, Style::default(), names)));
+            }
+            Block::Quote(inlines) => {
+                let mut spans = vec![Span::styled("▌ ", Style::default().fg(Color::DarkGray))];
+                spans.extend(render_inlines(inlines, Style::default().fg(Color::Gray), names));
+                lines.push(Spans::from(spans));
+            }
+            Block::Code(code_lines) => {
+                lines.extend(code_lines.iter().map(|line| Spans::from(vec![
+                    Span::styled("│ ", Style::default().fg(Color::DarkGray)),
+                    Span::styled(line.clone(), Style::default().fg(Color::LightRed)),
+                ])));
+            }
+        }
+    }
+    lines
+}
+
+pub fn render_inlines(inlines: &[Inline], style: Style, names: &Names<'_>) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => spans.push(Span::styled(text.clone(), style)),
+            Inline::Bold(children) => spans.extend(
+                render_inlines(children, style.add_modifier(Modifier::BOLD), names)),
+            Inline::Italic(children) => spans.extend(
+                render_inlines(children, style.add_modifier(Modifier::ITALIC), names)),
+            Inline::Strike(children) => spans.extend(
+                render_inlines(children, style.add_modifier(Modifier::CROSSED_OUT), names)),
+            Inline::Code(code) => spans.push(Span::styled(code.clone(), style.fg(Color::LightRed))),
+            Inline::UserMention { id, label } => {
+                let name = match names.users.get(id) {
+                    Some(user) => user.get_display_name(),
+                    None => label.as_deref().unwrap_or(id),
+                };
+                let style = match names.user_id == Some(id.as_str()) {
+                    true => style.fg(Color::Black).bg(Color::Yellow),
+                    false => style.fg(Color::Yellow),
+                };
+                spans.push(Span::styled(format!("@{}", name.trim_start_matches('@')),
+                    style.add_modifier(Modifier::BOLD)));
+            }
+            Inline::ChannelLink { id, label } => {
+                let name = names.channels.iter()
+                    .find(|channel| &channel.id == id)
+                    .map(|channel| channel.name.as_str())
+                    .or(label.as_deref())
+                    .unwrap_or(id);
+                spans.push(Span::styled(format!("#{}", name), style.fg(Color::Cyan)));
+            }
+            Inline::Special { name, label } => {
+                // <!subteam^S123|@team> and <!date^...|fallback> carry their text in the label
+                let text = match label {
+                    Some(label) => label.clone(),
+                    None => format!("@{}", name),
+                };
+                spans.push(Span::styled(text, style.fg(Color::Yellow).add_modifier(Modifier::BOLD)));
+            }
+            Inline::Link { url, label } => {
+                let text = label.clone().unwrap_or_else(|| url.trim_start_matches("mailto:").to_string());
+                spans.push(Span::styled(text,
+                    style.fg(Color::Blue).add_modifier(Modifier::UNDERLINED)));
+            }
+        }
+    }
+    spans
+}
+
+// Ids of the users mentioned in a message
+pub fn get_user_mentions(text: &str) -> Vec<String> {
+    text.split("<@").skip(1)
+        .filter_map(|part| part.split(['>', '|']).next())
+        .map(|id| id.to_string())
+        .collect()
+}
+
+// Mentions of the user with the id `user_id`, <@U123> or <@U123|name>
+pub fn has_user_mention(text: &str, user_id: &str) -> bool {
+    get_user_mentions(text).iter().any(|id| id == user_id)
+}
+
+// Mentions of everyone in the conversation, <!here>, <!channel> and <!everyone>
+pub fn has_broadcast_mention(text: &str) -> bool {
+    ["<!here", "<!channel", "<!everyone"].iter().any(|mention| text.contains(mention))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    const HISTORY: &str = include_str!("../fixtures/conversations.history.json");
+
+    fn text(text: &str) -> Inline {
+        Inline::Text(text.to_string())
+    }
+
+    fn line(inlines: Vec<Inline>) -> Vec<Block> {
+        vec![Block::Line(inlines)]
+    }
+
+    // Message text and the blocks it parses into
+    fn get_cases() -> Vec<(&'static str, Vec<Block>)> {
+        vec![
+            ("*bold* _italic_ ~strike~", line(vec![
+                Inline::Bold(vec![text("bold")]),
+                text(" "),
+                Inline::Italic(vec![text("italic")]),
+                text(" "),
+                Inline::Strike(vec![text("strike")]),
+            ])),
+            ("*bold _and italic_*", line(vec![
+                Inline::Bold(vec![text("bold "), Inline::Italic(vec![text("and italic")])]),
+            ])),
+            ("run `cargo *test*` now", line(vec![
+                text("run "),
+                Inline::Code("cargo *test*".to_string()),
+                text(" now"),
+            ])),
+            ("```\nfn main() {\n    a &lt; b\n}\n```", vec![
+                Block::Code(vec!["fn main() {".to_string(), "    a < b".to_string(), "}".to_string()]),
+            ]),
+            ("before\n```\ncode\n```\nafter", vec![
+                Block::Line(vec![text("before")]),
+                Block::Code(vec!["code".to_string()]),
+                Block::Line(vec![text("after")]),
+            ]),
+            ("&gt; quoted\n> also *quoted*", vec![
+                Block::Quote(vec![text("quoted")]),
+                Block::Quote(vec![text("also "), Inline::Bold(vec![text("quoted")])]),
+            ]),
+            ("<@U123> and <@U456|pete>", line(vec![
+                Inline::UserMention { id: "U123".to_string(), label: None },
+                text(" and "),
+                Inline::UserMention { id: "U456".to_string(), label: Some("pete".to_string()) },
+            ])),
+            ("see <#C123|general>", line(vec![
+                text("see "),
+                Inline::ChannelLink { id: "C123".to_string(), label: Some("general".to_string()) },
+            ])),
+            ("<!here> <!channel>", line(vec![
+                Inline::Special { name: "here".to_string(), label: None },
+                text(" "),
+                Inline::Special { name: "channel".to_string(), label: None },
+            ])),
+            ("<https://example.com?a=1&amp;b=2|Example> <mailto:a@b.c>", line(vec![
+                Inline::Link { url: "https://example.com?a=1&b=2".to_string(), label: Some("Example".to_string()) },
+                text(" "),
+                Inline::Link { url: "mailto:a@b.c".to_string(), label: None },
+            ])),
+            ("a &lt;b&gt; &amp;amp; c", line(vec![text("a <b> &amp; c")])),
+            // Delimiters inside words are text
+            ("snake_case_name", line(vec![text("snake_case_name")])),
+            ("2*3*4", line(vec![text("2*3*4")])),
+            ("* not bold *", line(vec![text("* not bold *")])),
+            // Unclosed markup is kept as it is
+            ("```unclosed\ncode", vec![
+                Block::Line(vec![text("```unclosed")]),
+                Block::Line(vec![text("code")]),
+            ]),
+            ("a < b and <@U1", line(vec![text("a < b and <@U1")])),
+            ("*unclosed bold", line(vec![text("*unclosed bold")])),
+        ]
+    }
+
+    #[test]
+    fn parses_cases() {
+        for (input, expected) in get_cases() {
+            assert_eq!(parse(input), expected, "parsing {:?}", input);
+        }
+    }
+
+    #[test]
+    fn parses_fixture_messages() {
+        let history: Value = serde_json::from_str(HISTORY).expect("Parse history fixture expect");
+        let texts: Vec<&str> = history["messages"].as_array().expect("Fixture messages expect")
+            .iter()
+            .filter_map(|message| message["text"].as_str())
+            .collect();
+
+        let blocks = parse(texts[0]);
+        assert_eq!(blocks.len(), 3);
+        assert!(matches!(&blocks[0], Block::Line(inlines) if inlines.len() == 9));
+        assert_eq!(blocks[1], Block::Quote(vec![text("Total protonic reversal")]));
+        assert!(matches!(&blocks[2], Block::Code(lines) if lines.len() == 3));
+
+        for text in &texts[1..] {
+            assert_eq!(parse(text), line(vec![Inline::Text(text.to_string())]));
+        }
+    }
+
+    #[test]
+    fn finds_mentions() {
+        let cases = [
+            ("hi <@U1> and <@U2|pete>", vec!["U1", "U2"], false),
+            ("<!here> look", vec![], true),
+            ("<!channel|@channel>", vec![], true),
+            ("no mentions, a < b", vec![], false),
+        ];
+        for (input, users, broadcast) in cases {
+            assert_eq!(get_user_mentions(input), users, "mentions in {:?}", input);
+            assert_eq!(has_broadcast_mention(input), broadcast, "broadcast in {:?}", input);
+        }
+        assert!(has_user_mention("<@U2|pete>", "U2"));
+        assert!(!has_user_mention("<@U21>", "U2"));
+    }
+}
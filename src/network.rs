@@ -0,0 +1,228 @@
+use crossterm::event::KeyEvent;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::InputEvent;
+use crate::slack_interface::{
+    Result,
+    auth_interface::{self, Identity},
+    channel_interface::{self, Channel, ReadState},
+    messages_interface::{self, Message},
+    rate_limiter::{self, Priority},
+    reactions_interface,
+    team_interface::{self, Team},
+    user_interface::{self, User},
+};
+
+// Requests sent from the UI to the network worker
+pub enum ApiRequest {
+    TestAuth,
+    LoadTeam,
+    LoadChannels,
+    LoadUsers,
+    LoadMessages { conversation_id: String },
+    LoadOlderMessages { conversation_id: String, cursor: String },
+    OpenDirectMessage { user_id: String, user_name: String },
+    LoadThread { conversation_id: String, thread_ts: String },
+    // A reply when thread_ts is set
+    SendMessage {
+        conversation_id: String,
+        text: String,
+        thread_ts: Option<String>,
+        reply_broadcast: bool,
+    },
+    PollMessages { conversation_id: String, oldest: String },
+    CheckUnread { conversation_id: String, oldest: String },
+    LoadReadState { conversation_id: String },
+    MarkRead { conversation_id: String, ts: String },
+    LookupUser { user_id: String },
+    AddReaction { conversation_id: String, ts: String, name: String },
+    RemoveReaction { conversation_id: String, ts: String, name: String },
+    LoadEmoji,
+    UpdateMessage { conversation_id: String, ts: String, text: String },
+    DeleteMessage { conversation_id: String, ts: String },
+}
+
+// Responses posted back to the UI as InputEvent::Api with the index of the workspace
+pub enum ApiResponse {
+    Auth(Result<Identity>),
+    Team(Result<Team>),
+    Channels(Result<Vec<Channel>>),
+    Users(Result<Vec<User>>),
+    Messages {
+        conversation_id: String,
+        result: Result<(Vec<Message>, Option<String>)>,
+    },
+    OlderMessages {
+        conversation_id: String,
+        result: Result<(Vec<Message>, Option<String>)>,
+    },
+    DirectMessageOpened {
+        user_name: String,
+        result: Result<String>,
+    },
+    Thread {
+        conversation_id: String,
+        thread_ts: String,
+        result: Result<Vec<Message>>,
+    },
+    MessageSent {
+        conversation_id: String,
+        text: String,
+        result: Result<Message>,
+    },
+    NewMessages {
+        conversation_id: String,
+        result: Result<Vec<Message>>,
+    },
+    // The newest page of messages since the last seen one, and whether there are more
+    UnreadChecked {
+        conversation_id: String,
+        result: Result<(Vec<Message>, bool)>,
+    },
+    // The read state with the newest page of unread messages, and whether there are more
+    ReadState {
+        conversation_id: String,
+        result: Result<(ReadState, Vec<Message>, bool)>,
+    },
+    ReadMarked {
+        conversation_id: String,
+        result: Result<()>,
+    },
+    UserInfo {
+        user_id: String,
+        result: Result<User>,
+    },
+    // The message with its reactions after adding or removing one
+    ReactionUpdated {
+        conversation_id: String,
+        result: Result<Message>,
+    },
+    Emoji(Result<Vec<String>>),
+    MessageUpdated {
+        conversation_id: String,
+        ts: String,
+        text: String,
+        result: Result<()>,
+    },
+    MessageDeleted {
+        conversation_id: String,
+        ts: String,
+        result: Result<()>,
+    },
+}
+
+// Start the network worker thread of a workspace. Requests are handled in order and every
+// response is posted into the same event stream as the input events. The worker stops when
+// the returned sender is dropped
+pub fn start_worker(oauth_token: String, page_limit: u64, workspace: usize, priority: Priority,
+    events: mpsc::Sender<InputEvent<KeyEvent>>) -> mpsc::Sender<ApiRequest> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        rate_limiter::set_priority(priority);
+        for request in rx {
+            let response = handle_request(request, &oauth_token, page_limit);
+            if events.send(InputEvent::Api(workspace, response)).is_err() {
+                break;
+            }
+        }
+    });
+
+    tx
+}
+
+fn handle_request(request: ApiRequest, oauth_token: &str, page_limit: u64) -> ApiResponse {
+    match request {
+        ApiRequest::TestAuth => {
+            ApiResponse::Auth(auth_interface::test_auth(oauth_token))
+        }
+        ApiRequest::LoadTeam => {
+            ApiResponse::Team(team_interface::get_team_info(oauth_token))
+        }
+        ApiRequest::LoadChannels => {
+            ApiResponse::Channels(channel_interface::get_channel_list(oauth_token, page_limit))
+        }
+        ApiRequest::LoadUsers => {
+            ApiResponse::Users(user_interface::get_user_list(oauth_token, page_limit))
+        }
+        ApiRequest::LoadMessages { conversation_id } => {
+            let result = messages_interface::get_channel_messages(
+                &conversation_id, oauth_token, page_limit, None);
+            ApiResponse::Messages { conversation_id, result }
+        }
+        ApiRequest::LoadOlderMessages { conversation_id, cursor } => {
+            let result = messages_interface::get_channel_messages(
+                &conversation_id, oauth_token, page_limit, Some(&cursor));
+            ApiResponse::OlderMessages { conversation_id, result }
+        }
+        ApiRequest::OpenDirectMessage { user_id, user_name } => {
+            let result = channel_interface::open_direct_message(&user_id, oauth_token);
+            ApiResponse::DirectMessageOpened { user_name, result }
+        }
+        ApiRequest::LoadThread { conversation_id, thread_ts } => {
+            let result = messages_interface::get_thread_replies(
+                &conversation_id, &thread_ts, oauth_token, page_limit);
+            ApiResponse::Thread { conversation_id, thread_ts, result }
+        }
+        ApiRequest::SendMessage { conversation_id, text, thread_ts, reply_broadcast } => {
+            let result = messages_interface::send_channel_message(
+                &text, &conversation_id, thread_ts.as_deref(), reply_broadcast, oauth_token);
+            ApiResponse::MessageSent { conversation_id, text, result }
+        }
+        ApiRequest::PollMessages { conversation_id, oldest } => {
+            let result = messages_interface::get_messages_since(
+                &conversation_id, oauth_token, page_limit, &oldest);
+            ApiResponse::NewMessages { conversation_id, result }
+        }
+        ApiRequest::CheckUnread { conversation_id, oldest } => {
+            let result = messages_interface::get_newest_messages(
+                &conversation_id, oauth_token, page_limit, &oldest);
+            ApiResponse::UnreadChecked { conversation_id, result }
+        }
+        ApiRequest::LoadReadState { conversation_id } => {
+            // The unread messages are needed to count mentions, and to count unread
+            // messages in channels where Slack does not. Only one page is read, a channel
+            // that was never read would otherwise pull its whole history
+            let result = channel_interface::get_read_state(&conversation_id, oauth_token)
+                .and_then(|state| {
+                    let (messages, has_more) = match state.unread_count {
+                        Some(0) => (Vec::new(), false),
+                        _ => messages_interface::get_newest_messages(
+                            &conversation_id, oauth_token, page_limit, &state.last_read)?,
+                    };
+                    Ok((state, messages, has_more))
+                });
+            ApiResponse::ReadState { conversation_id, result }
+        }
+        ApiRequest::MarkRead { conversation_id, ts } => {
+            let result = channel_interface::mark_read(&conversation_id, &ts, oauth_token);
+            ApiResponse::ReadMarked { conversation_id, result }
+        }
+        ApiRequest::LookupUser { user_id } => {
+            let result = user_interface::get_user_info(&user_id, oauth_token);
+            ApiResponse::UserInfo { user_id, result }
+        }
+        ApiRequest::AddReaction { conversation_id, ts, name } => {
+            let result = reactions_interface::add_reaction(&name, &conversation_id, &ts, oauth_token)
+                .and_then(|_| reactions_interface::get_reactions(&conversation_id, &ts, oauth_token));
+            ApiResponse::ReactionUpdated { conversation_id, result }
+        }
+        ApiRequest::RemoveReaction { conversation_id, ts, name } => {
+            let result = reactions_interface::remove_reaction(&name, &conversation_id, &ts, oauth_token)
+                .and_then(|_| reactions_interface::get_reactions(&conversation_id, &ts, oauth_token));
+            ApiResponse::ReactionUpdated { conversation_id, result }
+        }
+        ApiRequest::LoadEmoji => {
+            ApiResponse::Emoji(reactions_interface::get_custom_emoji(oauth_token))
+        }
+        ApiRequest::UpdateMessage { conversation_id, ts, text } => {
+            let result = messages_interface::update_message(&text, &conversation_id, &ts, oauth_token);
+            ApiResponse::MessageUpdated { conversation_id, ts, text, result }
+        }
+        ApiRequest::DeleteMessage { conversation_id, ts } => {
+            let result = messages_interface::delete_message(&conversation_id, &ts, oauth_token);
+            ApiResponse::MessageDeleted { conversation_id, ts, result }
+        }
+    }
+}
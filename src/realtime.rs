@@ -0,0 +1,131 @@
+use crossterm::event::KeyEvent;
+use serde_json::{json, Value};
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tungstenite::{stream::MaybeTlsStream, Message as WsMessage, WebSocket};
+
+use crate::InputEvent;
+use crate::slack_interface::realtime_interface::{self, RealtimeEvent};
+
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(2);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+// Start a thread that keeps a websocket connection to a workspace open and posts
+// every decoded event as InputEvent::Realtime with the index of the workspace
+pub fn start(token: String, workspace: usize, events: mpsc::Sender<InputEvent<KeyEvent>>) {
+    thread::spawn(move || {
+        let mut reconnect_delay = MIN_RECONNECT_DELAY;
+        loop {
+            let error = match realtime_interface::open_connection(&token) {
+                Ok(url) => {
+                    match tungstenite::connect(url.as_str()) {
+                        Ok((mut socket, _)) => {
+                            reconnect_delay = MIN_RECONNECT_DELAY;
+                            if events.send(InputEvent::Realtime(workspace, RealtimeEvent::Connected)).is_err() {
+                                return;
+                            }
+                            match listen(&mut socket, workspace, &events) {
+                                Some(error) => error,
+                                // The UI is gone
+                                None => return,
+                            }
+                        }
+                        Err(error) => error.to_string(),
+                    }
+                }
+                // Reconnecting will not help when the token is rejected
+                Err(error) if error.is_auth_error() => {
+                    let _ = events.send(InputEvent::Realtime(workspace, RealtimeEvent::Disconnected(error.to_string())));
+                    return;
+                }
+                Err(error) => error.to_string(),
+            };
+
+            if events.send(InputEvent::Realtime(workspace, RealtimeEvent::Disconnected(error))).is_err() {
+                return;
+            }
+            thread::sleep(reconnect_delay);
+            reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    });
+}
+
+// Read events until the connection closes, returns why it closed or None if the
+// event receiver has been dropped
+fn listen(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>, workspace: usize,
+    events: &mpsc::Sender<InputEvent<KeyEvent>>) -> Option<String> {
+    loop {
+        let text = match socket.read() {
+            Ok(WsMessage::Text(text)) => text,
+            Ok(WsMessage::Close(_)) => return Some("Connection closed".to_string()),
+            Ok(_) => continue,
+            Err(error) => return Some(error.to_string()),
+        };
+
+        let Ok(json) = serde_json::from_str::<Value>(&text) else {
+            continue;
+        };
+
+        // Acknowledge Socket Mode envelopes or Slack will send them again
+        if let Some(envelope_id) = realtime_interface::get_envelope_id(&json) {
+            let ack = json!({ "envelope_id": envelope_id }).to_string();
+            if let Err(error) = socket.send(WsMessage::Text(ack)) {
+                return Some(error.to_string());
+            }
+        }
+
+        if realtime_interface::is_disconnect(&json) {
+            return Some("Reconnect requested".to_string());
+        }
+
+        if let Some(event) = realtime_interface::parse_event(&json) {
+            if events.send(InputEvent::Realtime(workspace, event)).is_err() {
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slack_interface;
+    use std::time::Instant;
+
+    #[test]
+    fn acknowledges_envelopes_and_reconnects() {
+        let server = slack_interface::start_mock();
+        let (events, receiver) = mpsc::channel();
+        start("xapp-reconnect".to_string(), 3, events);
+
+        // The replay, the disconnect the mock sends after it and the next connection
+        let started = Instant::now();
+        let mut connections = 0;
+        let mut messages = 0;
+        let mut reconnect_requested = false;
+        while connections < 2 {
+            let timeout = Duration::from_secs(10).saturating_sub(started.elapsed());
+            match receiver.recv_timeout(timeout).expect("Realtime event expect") {
+                InputEvent::Realtime(3, RealtimeEvent::Connected) => connections += 1,
+                InputEvent::Realtime(3, RealtimeEvent::Message { .. }) if connections == 1 => messages += 1,
+                InputEvent::Realtime(3, RealtimeEvent::Disconnected(reason)) => {
+                    assert_eq!(reason, "Reconnect requested");
+                    reconnect_requested = true;
+                }
+                _ => {}
+            }
+        }
+        assert!(reconnect_requested);
+        assert_eq!(messages, 3);
+
+        // Every envelope of the first connection was acknowledged
+        let envelope_ids: Vec<String> = include_str!("../fixtures/realtime_events.jsonl").lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .filter_map(|event| event["envelope_id"].as_str().map(|id| id.to_string()))
+            .collect();
+        let acks = server.acks.lock().expect("Lock acks expect");
+        assert_eq!(acks["xapp-reconnect"][..envelope_ids.len()], envelope_ids[..]);
+    }
+}
@@ -0,0 +1,86 @@
+use serde_json::Value;
+
+use crate::slack_interface::{self, Result};
+
+// Who a token signs in as
+pub struct Identity{
+    pub user_id: String,
+    pub user: String,
+    pub team_id: String,
+    pub team: String,
+    pub url: String,
+}
+
+// Check the token, fails with "invalid_auth" or similar when it can not be used
+pub fn test_auth(token: &str) -> Result<Identity> {
+    let rsp = slack_interface::get(&slack_interface::api_url("auth.test"), token)?;
+    parse_identity(&rsp)
+}
+
+fn parse_identity(rsp: &Value) -> Result<Identity> {
+    Ok(Identity{
+        user_id: slack_interface::get_str(rsp, "user_id")?.to_string(),
+        user: rsp["user"].as_str().unwrap_or_default().to_string(),
+        team_id: slack_interface::get_str(rsp, "team_id")?.to_string(),
+        team: rsp["team"].as_str().unwrap_or_default().to_string(),
+        url: rsp["url"].as_str().unwrap_or_default().to_string(),
+    })
+}
+
+// Example response:
+// {
+//     "ok": true,
+//     "url": "https://subarachnoid.slack.com/",
+//     "team": "Subarachnoid Workspace",
+//     "user": "grace",
+//     "team_id": "T12345678",
+//     "user_id": "W12345678"
+// }
+
+// A user token from the OAuth flow
+pub struct OAuthAccess{
+    pub oauth_token: String,
+    pub user_id: String,
+    pub team_id: String,
+    pub team: String,
+}
+
+// Exchange the code from the OAuth redirect for a user token
+pub fn exchange_code(client_id: &str, client_secret: &str, code: &str, redirect_uri: &str)
+-> Result<OAuthAccess> {
+    let fields = [
+        ("client_id", client_id),
+        ("client_secret", client_secret),
+        ("code", code),
+        ("redirect_uri", redirect_uri),
+    ];
+    let rsp = slack_interface::post_form(&slack_interface::api_url("oauth.v2.access"), "", &fields)?;
+    parse_access(&rsp)
+}
+
+fn parse_access(rsp: &Value) -> Result<OAuthAccess> {
+    Ok(OAuthAccess{
+        oauth_token: slack_interface::get_str(&rsp["authed_user"], "access_token")?.to_string(),
+        user_id: slack_interface::get_str(&rsp["authed_user"], "id")?.to_string(),
+        team_id: slack_interface::get_str(&rsp["team"], "id")?.to_string(),
+        team: rsp["team"]["name"].as_str().unwrap_or_default().to_string(),
+    })
+}
+
+// Example response, the user token is in authed_user when only user scopes are requested:
+// {
+//     "ok": true,
+//     "app_id": "A0KRD7HC3",
+//     "authed_user": {
+//         "id": "U1234",
+//         "scope": "chat:write",
+//         "access_token": "xoxp-1234",
+//         "token_type": "user"
+//     },
+//     "team": {
+//         "name": "Slack Softball Team",
+//         "id": "T9TK3CUKW"
+//     },
+//     "enterprise": null,
+//     "is_enterprise_install": false
+// }
@@ -1,33 +1,130 @@
-use curl::easy::{Easy, List};
-use serde_json::{Result, Value};
+use serde_json::{json, Value};
 
-use crate::slack_interface;
+use crate::slack_interface::{self, Result};
+
+// The sections of the sidebar, in order
+pub const SECTIONS: [&str; 3] = ["Channels", "Direct Messages", "Group DMs"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind{
+    Public,
+    Private,
+    Im,
+    Mpim,
+}
+
+impl ChannelKind{
+    // Index into SECTIONS
+    pub fn get_section(&self) -> usize {
+        match self {
+            ChannelKind::Public | ChannelKind::Private => 0,
+            ChannelKind::Im => 1,
+            ChannelKind::Mpim => 2,
+        }
+    }
+}
 
 pub struct Channel{
     pub id: String,
+    // IMs have no name, the id of the other user is used
     pub name: String,
+    pub kind: ChannelKind,
+    pub is_member: bool,
+    pub is_archived: bool,
+    pub topic: String,
+    pub purpose: String,
+    pub num_members: u64,
+    // The other user of an IM
+    pub user: Option<String>,
 }
 
-pub fn get_channel_list(token: &str) -> Result<Vec<Channel>> {
-    // Send request to Slack API
-    let rsp = slack_interface::get("https://slack.com/api/conversations.list", token).expect("Get channel list expect");
-    let json_res = parse_channel_list(&rsp).expect("parse channel list expect");
+pub fn get_channel_list(token: &str, limit: u64) -> Result<Vec<Channel>> {
+    // Send request to Slack API, only public channels are returned without types
+    let url = slack_interface::api_url("conversations.list")
+        + "?types=public_channel,private_channel,mpim,im";
+    let channels_json = slack_interface::get_paginated(&url, token, "channels", limit)?;
+    parse_channel_list(&channels_json)
+}
 
-    Ok(json_res)
+fn parse_channel_list(channels_json: &[Value]) -> Result<Vec<Channel>> {
+    channels_json.iter().map(parse_channel).collect::<Result<Vec<Channel>>>()
 }
 
-fn parse_channel_list(json_rsp: &Value) -> Result<Vec<Channel>> {
-    let channels_json = json_rsp["channels"].as_array().unwrap();
-    let channels = channels_json.iter().map(|channel| {
-        let id = channel["id"].as_str().unwrap();
-        let name = channel["name"].as_str().unwrap();
-        Channel{
-            id: id.to_string(),
-            name: name.to_string(),
-        }
-    }).collect::<Vec<Channel>>();
+fn parse_channel(channel: &Value) -> Result<Channel> {
+    let id = slack_interface::get_str(channel, "id")?;
+    let user = channel["user"].as_str();
+    let kind = if channel["is_im"].as_bool().unwrap_or(false) {
+        ChannelKind::Im
+    } else if channel["is_mpim"].as_bool().unwrap_or(false) {
+        ChannelKind::Mpim
+    } else if channel["is_private"].as_bool().unwrap_or(false) {
+        ChannelKind::Private
+    } else {
+        ChannelKind::Public
+    };
+    let name = match (kind, user) {
+        (ChannelKind::Im, Some(user)) => user,
+        _ => slack_interface::get_str(channel, "name")?,
+    };
+
+    Ok(Channel{
+        id: id.to_string(),
+        name: name.to_string(),
+        kind,
+        // IMs are not listed with is_member, the user is always in them
+        is_member: channel["is_member"].as_bool().unwrap_or(kind == ChannelKind::Im),
+        is_archived: channel["is_archived"].as_bool().unwrap_or(false),
+        topic: channel["topic"]["value"].as_str().unwrap_or_default().to_string(),
+        purpose: channel["purpose"]["value"].as_str().unwrap_or_default().to_string(),
+        num_members: channel["num_members"].as_u64().unwrap_or(0),
+        user: user.map(|user| user.to_string()),
+    })
+}
+
+// The names of the users in a group DM, "mpdm-egon--peter--ray-1" has egon, peter and ray
+pub fn get_mpim_members(name: &str) -> Vec<&str> {
+    let name = name.strip_prefix("mpdm-").unwrap_or(name);
+    let name = name.rsplit_once('-').map(|(members, _)| members).unwrap_or(name);
+    name.split("--").collect()
+}
+
+// Where the user has read a conversation up to
+pub struct ReadState{
+    pub last_read: String,
+    // Only set for conversations where Slack counts unread messages, like IMs
+    pub unread_count: Option<u64>,
+}
+
+pub fn get_read_state(channel_id: &str, token: &str) -> Result<ReadState> {
+    let url = slack_interface::api_url("conversations.info") + "?channel=" + channel_id;
+    let rsp = slack_interface::get(&url, token)?;
+    let channel = &rsp["channel"];
+    Ok(ReadState{
+        last_read: slack_interface::get_str(channel, "last_read")?.to_string(),
+        unread_count: channel["unread_count_display"].as_u64(),
+    })
+}
+
+// Move the read cursor of a conversation, other clients show it as read as well
+pub fn mark_read(channel_id: &str, ts: &str, token: &str) -> Result<()> {
+    let body = json!({
+        "channel": channel_id,
+        "ts": ts,
+    });
+    slack_interface::post(&slack_interface::api_url("conversations.mark"), token, &body)?;
+    Ok(())
+}
+
+// Open a direct message with a user, returns the id of the IM conversation
+pub fn open_direct_message(user_id: &str, token: &str) -> Result<String> {
+    let body = json!({
+        "users": user_id,
+        "return_im": true,
+    });
 
-    Ok(channels)
+    let rsp = slack_interface::post(&slack_interface::api_url("conversations.open"), token, &body)?;
+    let id = slack_interface::get_str(&rsp["channel"], "id")?;
+    Ok(id.to_string())
 }
 
 // Example response:
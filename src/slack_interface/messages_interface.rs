@@ -1,48 +1,146 @@
-use serde_json::{Result, Value};
+use serde_json::{json, Value};
 
-use crate::slack_interface;
+use crate::slack_interface::{self, Result, SlackError};
+use crate::slack_interface::reactions_interface::{self, Reaction};
 
+#[derive(Clone)]
 pub struct Message {
     pub text: String,
     // channel: String,
     pub username: String,
     pub message_type: String,
+    pub subtype: Option<String>,
     pub ts: String,
+    // Set on thread parents and replies, parents have thread_ts == ts
+    pub thread_ts: Option<String>,
+    pub reply_count: u64,
+    // Ids of the users that replied
+    pub reply_users: Vec<String>,
+    pub reactions: Vec<Reaction>,
+    pub is_edited: bool,
     // icon_emoji: String,
     // attachments: Vec<attachment>,
 }
 
-pub fn get_channel_messages(channel_id: &str, oauth_token: &str) -> Result<Vec<Message>> {
-    let url = "https://slack.com/api/conversations.history?channel=".to_string() + channel_id;
-    let json_res = slack_interface::get(&url, oauth_token).expect("Get channel messages expect");
-    let res = parse_messages(&json_res).expect("parse messages expect");
+impl Message {
+    // Count a reaction, returns false if the user already had it
+    pub fn add_reaction(&mut self, name: &str, user_id: &str) -> bool {
+        match self.reactions.iter_mut().find(|reaction| reaction.name == name) {
+            Some(reaction) => reaction.add_user(user_id),
+            None => {
+                self.reactions.push(Reaction{
+                    name: name.to_string(),
+                    count: 1,
+                    users: vec![user_id.to_string()],
+                });
+                true
+            }
+        }
+    }
+
+    pub fn remove_reaction(&mut self, name: &str, user_id: &str) -> bool {
+        let Some(reaction) = self.reactions.iter_mut().find(|reaction| reaction.name == name) else {
+            return false;
+        };
+        let removed = reaction.remove_user(user_id);
+        self.reactions.retain(|reaction| reaction.count > 0);
+        removed
+    }
+
+    // A reply inside a thread, not the parent message
+    pub fn is_thread_reply(&self) -> bool {
+        self.thread_ts.as_ref().is_some_and(|thread_ts| *thread_ts != self.ts)
+    }
 
-    Ok(res)
+    // A reply that was also sent to the channel
+    pub fn is_broadcast(&self) -> bool {
+        self.subtype.as_deref() == Some("thread_broadcast")
+    }
 }
 
-fn parse_messages(json_rsp: &Value) -> Result<Vec<Message>>{
-    //  TODO: Make sure to check if json_rsp is not None when unwrap or it will panic
-    let messages = json_rsp["messages"].as_array().unwrap();
-    let messages = messages.iter().map(|message| {
-        let text = message["text"].as_str().unwrap();
-        let username = message["user"].as_str().unwrap();
-        let ts = message["ts"].as_str().unwrap();
-        let message_type = message["type"].as_str().unwrap();
-        // let icon_emoji = message["icon_emoji"].as_str().unwrap();
-        // let attachments = message["attachments"].as_array().unwrap();
-        Message{
-            text: text.to_string(),
-            username: username.to_string(),
-            ts: ts.to_string(),
-            message_type: message_type.to_string(),
-            // icon_emoji: icon_emoji.to_string(),
-            // attachments: attachments,
-        }
-    }).collect::<Vec<Message>>();
-    
+// Get one page of history, newest message first. The returned cursor points to older messages
+pub fn get_channel_messages(channel_id: &str, oauth_token: &str, limit: u64, cursor: Option<&str>)
+-> Result<(Vec<Message>, Option<String>)> {
+    let url = slack_interface::api_url("conversations.history") + "?channel=" + channel_id;
+    let (messages, next_cursor) = slack_interface::get_page(
+        &url, oauth_token, "messages", limit, cursor)?;
+    Ok((parse_messages(&messages), next_cursor))
+}
+
+// Get all messages newer than `oldest`, newest message first. `limit` is the page size
+pub fn get_messages_since(channel_id: &str, oauth_token: &str, limit: u64, oldest: &str)
+-> Result<Vec<Message>> {
+    let url = format!("{}?channel={}&oldest={}&inclusive=false",
+        slack_interface::api_url("conversations.history"), channel_id, oldest);
+    let messages = slack_interface::get_paginated(&url, oauth_token, "messages", limit)?;
+    Ok(parse_messages(&messages))
+}
+
+// Get the newest messages after `oldest`, at most `limit` of them. Also returns whether there
+// are more, without going through the rest of the history
+pub fn get_newest_messages(channel_id: &str, oauth_token: &str, limit: u64, oldest: &str)
+-> Result<(Vec<Message>, bool)> {
+    let url = format!("{}?channel={}&oldest={}&inclusive=false",
+        slack_interface::api_url("conversations.history"), channel_id, oldest);
+    let (messages, next_cursor) = slack_interface::get_page(&url, oauth_token, "messages", limit, None)?;
+    Ok((parse_messages(&messages), next_cursor.is_some()))
+}
+
+// Get a thread, newest message first. The parent message is the last one
+pub fn get_thread_replies(channel_id: &str, thread_ts: &str, oauth_token: &str, limit: u64)
+-> Result<Vec<Message>> {
+    let url = format!("{}?channel={}&ts={}",
+        slack_interface::api_url("conversations.replies"), channel_id, thread_ts);
+    let messages = slack_interface::get_paginated(&url, oauth_token, "messages", limit)?;
+    // conversations.replies returns the thread oldest first
+    let mut messages = parse_messages(&messages);
+    messages.reverse();
     Ok(messages)
 }
 
+// A message that can not be parsed is left out instead of failing the whole page
+fn parse_messages(messages: &[Value]) -> Vec<Message>{
+    messages.iter().filter_map(|message| parse_message(message).ok()).collect()
+}
+
+pub fn parse_message(message: &Value) -> Result<Message>{
+    let text = message["text"].as_str().unwrap_or_default();
+    // Bot messages have no user, fall back to the bot name or id
+    let username = message["user"].as_str()
+        .or_else(|| message["username"].as_str())
+        .or_else(|| message["bot_id"].as_str())
+        .ok_or_else(|| SlackError::MissingField("user".to_string()))?;
+    let ts = slack_interface::get_str(message, "ts")?;
+    let message_type = slack_interface::get_str(message, "type")?;
+    // Older responses list the replies instead of reply_users
+    let reply_users = match message["reply_users"].as_array() {
+        Some(users) => users.iter()
+            .filter_map(|user| user.as_str())
+            .map(|user| user.to_string())
+            .collect(),
+        None => message["replies"].as_array().into_iter().flatten()
+            .filter_map(|reply| reply["user"].as_str())
+            .map(|user| user.to_string())
+            .collect(),
+    };
+    // let icon_emoji = message["icon_emoji"].as_str().unwrap();
+    // let attachments = message["attachments"].as_array().unwrap();
+    Ok(Message{
+        text: text.to_string(),
+        username: username.to_string(),
+        ts: ts.to_string(),
+        message_type: message_type.to_string(),
+        subtype: message["subtype"].as_str().map(|subtype| subtype.to_string()),
+        thread_ts: message["thread_ts"].as_str().map(|thread_ts| thread_ts.to_string()),
+        reply_count: message["reply_count"].as_u64().unwrap_or(0),
+        reply_users,
+        reactions: reactions_interface::parse_reactions(message),
+        is_edited: message["edited"].is_object(),
+        // icon_emoji: icon_emoji.to_string(),
+        // attachments: attachments,
+    })
+}
+
 // Example response:
 // {
 //     "ok": true,
@@ -67,22 +165,22 @@ fn parse_messages(json_rsp: &Value) -> Result<Vec<Message>>{
 //     }
 // }
 
-pub fn send_channel_message(text: &str, channel_id: &str, oauth_token: &str) -> Result<bool>{
-    // let formatted_text = format!("\"{}\"", text);    // Do this to handle spaces
-    let url = format!(
-        "https://slack.com/api/chat.postMessage?channel={}&text={}",
-        channel_id, text.replace(" ", "+"));
-
-    let json_res = slack_interface::get(&url, oauth_token)
-        .expect("Get channel messages expect");
-    let res = parse_send_channel_message(&json_res).expect("parse messages expect");
-
-    return Ok(res)
-}
+// Send a message, or a reply when `thread_ts` is set. Replies can also be broadcast to the channel.
+// Returns the message as Slack posted it
+pub fn send_channel_message(text: &str, channel_id: &str, thread_ts: Option<&str>,
+    reply_broadcast: bool, oauth_token: &str) -> Result<Message>{
+    let mut body = json!({
+        "channel": channel_id,
+        "text": text,
+    });
+    if let Some(thread_ts) = thread_ts {
+        body["thread_ts"] = json!(thread_ts);
+        body["reply_broadcast"] = json!(reply_broadcast);
+    }
 
-fn parse_send_channel_message(json_rsp: &Value) -> Result<bool>{
-    let rsp = json_rsp["ok"].as_bool().unwrap();
-    Ok(rsp)
+    let json_res = slack_interface::post(
+        &slack_interface::api_url("chat.postMessage"), oauth_token, &body)?;
+    parse_message(&json_res["message"])
 }
 
 // Example sucess response
@@ -105,4 +203,24 @@ fn parse_send_channel_message(json_rsp: &Value) -> Result<bool>{
 //         "subtype": "bot_message",
 //         "ts": "1503435956.000247"
 //     }
-// }
\ No newline at end of file
+// }
+
+// Replace the text of a message, only the author can do this
+pub fn update_message(text: &str, channel_id: &str, ts: &str, oauth_token: &str) -> Result<()>{
+    let body = json!({
+        "channel": channel_id,
+        "ts": ts,
+        "text": text,
+    });
+    slack_interface::post(&slack_interface::api_url("chat.update"), oauth_token, &body)?;
+    Ok(())
+}
+
+pub fn delete_message(channel_id: &str, ts: &str, oauth_token: &str) -> Result<()>{
+    let body = json!({
+        "channel": channel_id,
+        "ts": ts,
+    });
+    slack_interface::post(&slack_interface::api_url("chat.delete"), oauth_token, &body)?;
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Requests per minute for each Slack rate limit tier
+// https://api.slack.com/docs/rate-limits
+const TIER_2: f64 = 20.0;
+const TIER_3: f64 = 50.0;
+const TIER_4: f64 = 100.0;
+const POST_MESSAGE: f64 = 60.0;
+
+// Share of every bucket that background requests leave to interactive ones
+const INTERACTIVE_RESERVE: f64 = 0.2;
+
+// Slack counts the limits per workspace and token, so every token gets its own buckets
+static RATE_LIMITER: OnceLock<Mutex<HashMap<(String, String), TokenBucket>>> = OnceLock::new();
+
+// Background requests only take tokens above the reserve and never queue up, so they can
+// not hold back the requests the user is waiting for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    Interactive,
+    Background,
+}
+
+thread_local! {
+    static PRIORITY: Cell<Priority> = const { Cell::new(Priority::Interactive) };
+}
+
+// Set the priority of the requests made from the current thread
+pub fn set_priority(priority: Priority) {
+    PRIORITY.with(|current| current.set(priority));
+}
+
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_second: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(requests_per_minute: f64) -> Self {
+        TokenBucket {
+            capacity: requests_per_minute,
+            tokens: requests_per_minute,
+            refill_per_second: requests_per_minute / 60.0,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        // A pause from Retry-After can put the last refill in the future
+        if now > self.last_refill {
+            let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.refill_per_second).min(self.capacity);
+            self.last_refill = now;
+        }
+    }
+
+    // Take a token, the returned duration is how long the caller has to wait for it.
+    // Tokens can go negative so that waiting callers queue up behind each other
+    fn take(&mut self) -> Duration {
+        self.refill();
+        self.tokens -= 1.0;
+
+        let mut wait = 0.0;
+        if self.tokens < 0.0 {
+            wait += -self.tokens / self.refill_per_second;
+        }
+        let now = Instant::now();
+        if self.last_refill > now {
+            wait += self.last_refill.duration_since(now).as_secs_f64();
+        }
+        Duration::from_secs_f64(wait)
+    }
+
+    // Take a token only when more than the reserve is left, otherwise return how long it
+    // takes until there is
+    fn take_spare(&mut self) -> Result<(), Duration> {
+        self.refill();
+        let reserve = self.capacity * INTERACTIVE_RESERVE;
+        let now = Instant::now();
+        if self.tokens >= reserve + 1.0 && self.last_refill <= now {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+
+        let mut wait = (reserve + 1.0 - self.tokens).max(0.0) / self.refill_per_second;
+        if self.last_refill > now {
+            wait += self.last_refill.duration_since(now).as_secs_f64();
+        }
+        Err(Duration::from_secs_f64(wait))
+    }
+
+    // Stop handing out tokens until `duration` has passed
+    fn pause(&mut self, duration: Duration) {
+        self.refill();
+        self.last_refill = Instant::now() + duration;
+    }
+}
+
+fn requests_per_minute(method: &str) -> f64 {
+    match method {
+        "chat.postMessage" => POST_MESSAGE,
+        "conversations.list" | "users.list" | "emoji.list" => TIER_2,
+        "users.info" | "auth.test" | "chat.update" | "chat.delete" => TIER_4,
+        _ => TIER_3,
+    }
+}
+
+// Get the API method from a url, "https://slack.com/api/users.list?limit=1" -> "users.list"
+pub fn method_name(url: &str) -> &str {
+    let path = url.split('?').next().unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+// Block until a request to `method` with `token` is allowed
+pub fn acquire(token: &str, method: &str) {
+    let priority = PRIORITY.with(Cell::get);
+    loop {
+        let wait = {
+            let mut buckets = RATE_LIMITER.get_or_init(|| Mutex::new(HashMap::new()))
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let bucket = buckets.entry((token.to_string(), method.to_string()))
+                .or_insert_with(|| TokenBucket::new(requests_per_minute(method)));
+            match priority {
+                Priority::Interactive => Ok(bucket.take()),
+                // Try again after the wait, interactive requests may have taken the tokens
+                Priority::Background => bucket.take_spare().map(|_| Duration::ZERO),
+            }
+        };
+
+        match wait {
+            Ok(wait) => {
+                if !wait.is_zero() {
+                    thread::sleep(wait);
+                }
+                return;
+            }
+            Err(wait) => thread::sleep(wait),
+        }
+    }
+}
+
+// Slack answered 429, hold back all requests to `method` with `token` for `duration`
+pub fn pause(token: &str, method: &str, duration: Duration) {
+    let mut buckets = RATE_LIMITER.get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    buckets.entry((token.to_string(), method.to_string()))
+        .or_insert_with(|| TokenBucket::new(requests_per_minute(method)))
+        .pause(duration);
+}
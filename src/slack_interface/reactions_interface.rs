@@ -0,0 +1,100 @@
+use serde_json::{json, Value};
+
+use crate::slack_interface::{self, Result};
+use crate::slack_interface::messages_interface::{self, Message};
+
+#[derive(Clone)]
+pub struct Reaction{
+    pub name: String,
+    pub count: u64,
+    // Ids of the users that reacted, Slack may not list all of them
+    pub users: Vec<String>,
+}
+
+impl Reaction{
+    // Count a reaction from `user_id`, returns false if it was already counted
+    pub fn add_user(&mut self, user_id: &str) -> bool {
+        if self.users.iter().any(|user| user == user_id) {
+            return false;
+        }
+        self.users.push(user_id.to_string());
+        self.count += 1;
+        true
+    }
+
+    // Remove a reaction from `user_id`, returns false if it was not counted
+    pub fn remove_user(&mut self, user_id: &str) -> bool {
+        let users = self.users.len();
+        self.users.retain(|user| user != user_id);
+        if self.users.len() == users {
+            return false;
+        }
+        self.count = self.count.saturating_sub(1);
+        true
+    }
+}
+
+// Parse the reactions of a message, messages without reactions have no field
+pub fn parse_reactions(message: &Value) -> Vec<Reaction> {
+    message["reactions"].as_array().into_iter().flatten()
+        .filter_map(|reaction| {
+            let users: Vec<String> = reaction["users"].as_array().into_iter().flatten()
+                .filter_map(|user| user.as_str())
+                .map(|user| user.to_string())
+                .collect();
+            Some(Reaction{
+                name: reaction["name"].as_str()?.to_string(),
+                count: reaction["count"].as_u64().unwrap_or(users.len() as u64),
+                users,
+            })
+        })
+        .collect()
+}
+
+pub fn add_reaction(name: &str, channel_id: &str, ts: &str, token: &str) -> Result<()> {
+    let body = json!({
+        "channel": channel_id,
+        "timestamp": ts,
+        "name": name,
+    });
+    slack_interface::post(&slack_interface::api_url("reactions.add"), token, &body)?;
+    Ok(())
+}
+
+pub fn remove_reaction(name: &str, channel_id: &str, ts: &str, token: &str) -> Result<()> {
+    let body = json!({
+        "channel": channel_id,
+        "timestamp": ts,
+        "name": name,
+    });
+    slack_interface::post(&slack_interface::api_url("reactions.remove"), token, &body)?;
+    Ok(())
+}
+
+// Get a message with its current reactions
+pub fn get_reactions(channel_id: &str, ts: &str, token: &str) -> Result<Message> {
+    let url = format!("{}?channel={}&timestamp={}&full=true",
+        slack_interface::api_url("reactions.get"), channel_id, ts);
+    let rsp = slack_interface::get(&url, token)?;
+    messages_interface::parse_message(&rsp["message"])
+}
+
+// Get the names of the custom emoji of the workspace, aliases included
+pub fn get_custom_emoji(token: &str) -> Result<Vec<String>> {
+    let rsp = slack_interface::get(&slack_interface::api_url("emoji.list"), token)?;
+    let emoji = rsp["emoji"].as_object()
+        .ok_or_else(|| slack_interface::SlackError::MissingField("emoji".to_string()))?;
+    let mut names: Vec<String> = emoji.keys().cloned().collect();
+    names.sort();
+    Ok(names)
+}
+
+// Example emoji.list response:
+// {
+//     "ok": true,
+//     "emoji": {
+//         "bowtie": "https://my.slack.com/emoji/bowtie/46ec6f2bb0.png",
+//         "squirrel": "https://my.slack.com/emoji/squirrel/f35f40c0e0.png",
+//         "shipit": "alias:squirrel"
+//     }
+// }
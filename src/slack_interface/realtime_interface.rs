@@ -0,0 +1,223 @@
+use serde_json::{json, Value};
+
+use crate::slack_interface::{self, Result};
+use crate::slack_interface::messages_interface::{self, Message};
+
+pub enum RealtimeEvent {
+    Connected,
+    Disconnected(String),
+    Message { channel: String, message: Message },
+    MessageChanged { channel: String, message: Message },
+    MessageDeleted { channel: String, ts: String },
+    ReactionAdded { channel: String, ts: String, user: String, reaction: String },
+    ReactionRemoved { channel: String, ts: String, user: String, reaction: String },
+    UserTyping { channel: String, user: String },
+    PresenceChange { user: String, presence: String },
+    // The conversation was read up to ts, possibly in another client
+    ConversationMarked { channel: String, ts: String, unread_count: u64 },
+}
+
+// Get a websocket url. App level tokens (xapp-) connect with Socket Mode,
+// other tokens fall back to the RTM API
+pub fn open_connection(token: &str) -> Result<String> {
+    let rsp = if token.starts_with("xapp-") {
+        slack_interface::post(&slack_interface::api_url("apps.connections.open"), token, &json!({}))?
+    } else {
+        slack_interface::get(&slack_interface::api_url("rtm.connect"), token)?
+    };
+
+    let url = slack_interface::get_str(&rsp, "url")?;
+    Ok(url.to_string())
+}
+
+// Socket Mode wraps events in envelopes that have to be acknowledged
+pub fn get_envelope_id(json: &Value) -> Option<&str> {
+    json["envelope_id"].as_str()
+}
+
+// Socket Mode asks clients to reconnect before it closes the connection
+pub fn is_disconnect(json: &Value) -> bool {
+    json["type"] == "disconnect"
+}
+
+// Decode a Socket Mode envelope or an RTM event, returns None for events we do not handle
+pub fn parse_event(json: &Value) -> Option<RealtimeEvent> {
+    let event = if json["payload"]["event"].is_object() {
+        &json["payload"]["event"]
+    } else {
+        json
+    };
+
+    let field = |name: &str| event[name].as_str().map(|value| value.to_string());
+    match event["type"].as_str()? {
+        "message" => {
+            let channel = field("channel")?;
+            match event["subtype"].as_str() {
+                // message_replied carries the parent with the new reply count
+                Some("message_changed") | Some("message_replied") => {
+                    let message = messages_interface::parse_message(&event["message"]).ok()?;
+                    Some(RealtimeEvent::MessageChanged { channel, message })
+                }
+                Some("message_deleted") => {
+                    let ts = field("deleted_ts")?;
+                    Some(RealtimeEvent::MessageDeleted { channel, ts })
+                }
+                _ => {
+                    let message = messages_interface::parse_message(event).ok()?;
+                    Some(RealtimeEvent::Message { channel, message })
+                }
+            }
+        }
+        "reaction_added" => Some(RealtimeEvent::ReactionAdded {
+            channel: event["item"]["channel"].as_str()?.to_string(),
+            ts: event["item"]["ts"].as_str()?.to_string(),
+            user: field("user")?,
+            reaction: field("reaction")?,
+        }),
+        "reaction_removed" => Some(RealtimeEvent::ReactionRemoved {
+            channel: event["item"]["channel"].as_str()?.to_string(),
+            ts: event["item"]["ts"].as_str()?.to_string(),
+            user: field("user")?,
+            reaction: field("reaction")?,
+        }),
+        "user_typing" => Some(RealtimeEvent::UserTyping {
+            channel: field("channel")?,
+            user: field("user")?,
+        }),
+        "presence_change" => Some(RealtimeEvent::PresenceChange {
+            user: field("user")?,
+            presence: field("presence")?,
+        }),
+        "channel_marked" | "group_marked" | "im_marked" | "mpim_marked" => {
+            Some(RealtimeEvent::ConversationMarked {
+                channel: field("channel")?,
+                ts: field("ts")?,
+                unread_count: event["unread_count_display"].as_u64().unwrap_or(0),
+            })
+        }
+        _ => None,
+    }
+}
+
+// Example Socket Mode envelope:
+// {
+//     "envelope_id": "dbdd0ef3-1543-4f94-bfb4-133d0e6c1545",
+//     "type": "events_api",
+//     "accepts_response_payload": false,
+//     "payload": {
+//         "type": "event_callback",
+//         "event": {
+//             "type": "message",
+//             "channel": "C012AB3CD",
+//             "user": "U061F7AUR",
+//             "text": "Hello world",
+//             "ts": "1512104434.000490"
+//         }
+//     }
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wrap an event in a Socket Mode envelope
+    fn envelope(event: Value) -> Value {
+        json!({
+            "envelope_id": "dbdd0ef3-1543-4f94-bfb4-133d0e6c1545",
+            "type": "events_api",
+            "payload": { "type": "event_callback", "event": event },
+        })
+    }
+
+    #[test]
+    fn parses_messages() {
+        let event = json!({
+            "type": "message",
+            "channel": "C012AB3CD",
+            "user": "U061F7AUR",
+            "text": "Hello world",
+            "ts": "1512104434.000490",
+        });
+        // The same event from Socket Mode and RTM
+        for json in [envelope(event.clone()), event] {
+            match parse_event(&json) {
+                Some(RealtimeEvent::Message { channel, message }) => {
+                    assert_eq!(channel, "C012AB3CD");
+                    assert_eq!(message.text, "Hello world");
+                    assert_eq!(message.ts, "1512104434.000490");
+                }
+                _ => panic!("Expected a message"),
+            }
+        }
+
+        let changed = envelope(json!({
+            "type": "message",
+            "subtype": "message_changed",
+            "channel": "C012AB3CD",
+            "ts": "1512105010.000200",
+            "message": { "type": "message", "user": "U061F7AUR", "text": "Edited", "ts": "1512104434.000490",
+                "edited": { "user": "U061F7AUR", "ts": "1512105010.000200" } },
+        }));
+        match parse_event(&changed) {
+            Some(RealtimeEvent::MessageChanged { channel, message }) => {
+                assert_eq!(channel, "C012AB3CD");
+                assert_eq!(message.ts, "1512104434.000490");
+                assert!(message.is_edited);
+            }
+            _ => panic!("Expected a changed message"),
+        }
+
+        let deleted = envelope(json!({
+            "type": "message",
+            "subtype": "message_deleted",
+            "channel": "C012AB3CD",
+            "ts": "1512105030.000400",
+            "deleted_ts": "1512104434.000490",
+        }));
+        assert!(matches!(parse_event(&deleted),
+            Some(RealtimeEvent::MessageDeleted { channel, ts }) if channel == "C012AB3CD" && ts == "1512104434.000490"));
+    }
+
+    #[test]
+    fn parses_reactions_and_typing() {
+        let reaction = |kind: &str| envelope(json!({
+            "type": kind,
+            "user": "U07QCRPA4",
+            "reaction": "ghost",
+            "item": { "type": "message", "channel": "C012AB3CD", "ts": "1512104434.000490" },
+        }));
+        assert!(matches!(parse_event(&reaction("reaction_added")),
+            Some(RealtimeEvent::ReactionAdded { channel, ts, user, reaction })
+                if channel == "C012AB3CD" && ts == "1512104434.000490" && user == "U07QCRPA4" && reaction == "ghost"));
+        assert!(matches!(parse_event(&reaction("reaction_removed")),
+            Some(RealtimeEvent::ReactionRemoved { reaction, .. }) if reaction == "ghost"));
+
+        let typing = json!({ "type": "user_typing", "channel": "C012AB3CD", "user": "U061F7AUR" });
+        assert!(matches!(parse_event(&typing),
+            Some(RealtimeEvent::UserTyping { channel, user }) if channel == "C012AB3CD" && user == "U061F7AUR"));
+
+        // Events without the fields we need are dropped
+        assert!(parse_event(&json!({ "type": "user_typing", "channel": "C012AB3CD" })).is_none());
+        assert!(parse_event(&json!({ "type": "team_join" })).is_none());
+    }
+
+    #[test]
+    fn parses_marked_conversations() {
+        for kind in ["channel_marked", "group_marked", "im_marked", "mpim_marked"] {
+            let marked = json!({
+                "type": kind,
+                "channel": "D061F7AUR",
+                "ts": "1512104434.000490",
+                "unread_count_display": 2,
+            });
+            assert!(matches!(parse_event(&marked),
+                Some(RealtimeEvent::ConversationMarked { channel, ts, unread_count: 2 })
+                    if channel == "D061F7AUR" && ts == "1512104434.000490"), "{}", kind);
+        }
+
+        let hello = json!({ "type": "hello" });
+        assert!(parse_event(&hello).is_none());
+        assert!(is_disconnect(&json!({ "type": "disconnect", "reason": "refresh_requested" })));
+        assert_eq!(get_envelope_id(&envelope(hello)), Some("dbdd0ef3-1543-4f94-bfb4-133d0e6c1545"));
+    }
+}
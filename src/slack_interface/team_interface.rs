@@ -0,0 +1,40 @@
+use serde_json::Value;
+
+use crate::slack_interface::{self, Result};
+
+pub struct Team{
+    pub id: String,
+    pub name: String,
+    pub domain: String,
+}
+
+// Get the workspace the token belongs to
+pub fn get_team_info(token: &str) -> Result<Team> {
+    let rsp = slack_interface::get(&slack_interface::api_url("team.info"), token)?;
+    parse_team(&rsp["team"])
+}
+
+fn parse_team(team: &Value) -> Result<Team> {
+    Ok(Team{
+        id: slack_interface::get_str(team, "id")?.to_string(),
+        name: slack_interface::get_str(team, "name")?.to_string(),
+        domain: team["domain"].as_str().unwrap_or_default().to_string(),
+    })
+}
+
+// Example response:
+// {
+//     "ok": true,
+//     "team": {
+//         "id": "T12345",
+//         "name": "My Team",
+//         "domain": "example",
+//         "email_domain": "example.com",
+//         "icon": {
+//             "image_34": "https://...",
+//             "image_default": true
+//         },
+//         "enterprise_id": "E1234A12AB",
+//         "enterprise_name": "Umbrella Corporation"
+//     }
+// }
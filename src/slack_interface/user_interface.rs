@@ -1,32 +1,104 @@
-use curl::easy::{Easy, List};
-use serde_json::{Result, Value};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 
-use crate::slack_interface;
+use crate::slack_interface::{self, Result};
 
+#[derive(Clone)]
 pub struct User{
     pub id: String,
     pub name: String,
+    pub real_name: String,
+    pub display_name: String,
+    pub color: Option<String>,
+    pub is_bot: bool,
 }
 
-pub fn get_user_list(token: &str) -> Result<Vec<User>> {
-    // Send request to Slack API
-    // TODO: Handle errors
-    let json_res = slack_interface::get("https://slack.com/api/users.list", token).expect("Get user list expect");
-    let json_res_parsed = parse_user_list(&json_res).expect("parse user list expect");
+impl User{
+    // The name Slack shows, display name first and the username as the last resort
+    pub fn get_display_name(&self) -> &str {
+        [&self.display_name, &self.real_name, &self.name].into_iter()
+            .find(|name| !name.is_empty())
+            .unwrap_or(&self.id)
+    }
+}
 
-    Ok(json_res_parsed)
+// Cache of known users by id, filled from users.list and users.info
+#[derive(Default)]
+pub struct UserDirectory{
+    users: HashMap<String, User>,
+    // Ids that have been looked up, so each unknown id is only requested once
+    requested: HashSet<String>,
 }
 
-fn parse_user_list(json_rsp: &Value) -> Result<Vec<User>> {
-    let members = json_rsp["members"].as_array().unwrap();
-    let users = members.iter().map(|member| {
-        let id = member["id"].as_str().unwrap();
-        let name = member["name"].as_str().unwrap();
-        User{
-            id: id.to_string(),
-            name: name.to_string(),
+impl UserDirectory{
+    pub fn insert(&mut self, user: User){
+        self.users.insert(user.id.clone(), user);
+    }
+
+    pub fn extend(&mut self, users: &[User]){
+        for user in users {
+            self.insert(user.clone());
         }
-    }).collect::<Vec<User>>();
+    }
+
+    pub fn get(&self, user_id: &str) -> Option<&User> {
+        self.users.get(user_id)
+    }
+
+    // Falls back to the id for users that are not known (yet)
+    pub fn get_display_name<'a>(&'a self, user_id: &'a str) -> &'a str {
+        self.get(user_id)
+            .map(|user| user.get_display_name())
+            .unwrap_or(user_id)
+    }
 
-    Ok(users)
-}
\ No newline at end of file
+    // Get the ids that are not known and have not been requested, and mark them as requested.
+    // Bot ids can not be looked up with users.info so only user ids are returned
+    pub fn take_unknown<'a>(&mut self, user_ids: impl Iterator<Item = &'a str>) -> Vec<String> {
+        let mut unknown = Vec::new();
+        for user_id in user_ids {
+            let is_user_id = user_id.starts_with('U') || user_id.starts_with('W');
+            if is_user_id && !self.users.contains_key(user_id) && self.requested.insert(user_id.to_string()) {
+                unknown.push(user_id.to_string());
+            }
+        }
+        unknown
+    }
+}
+
+pub fn get_user_list(token: &str, limit: u64) -> Result<Vec<User>> {
+    // Send request to Slack API
+    let members = slack_interface::get_paginated(
+        &slack_interface::api_url("users.list"), token, "members", limit)?;
+    parse_user_list(&members)
+}
+
+pub fn get_user_info(user_id: &str, token: &str) -> Result<User> {
+    let url = slack_interface::api_url("users.info") + "?user=" + user_id;
+    let rsp = slack_interface::get(&url, token)?;
+    parse_user(&rsp["user"])
+}
+
+fn parse_user_list(members: &[Value]) -> Result<Vec<User>> {
+    members.iter().map(parse_user).collect::<Result<Vec<User>>>()
+}
+
+fn parse_user(member: &Value) -> Result<User> {
+    let id = slack_interface::get_str(member, "id")?;
+    let name = slack_interface::get_str(member, "name")?;
+    let profile = &member["profile"];
+    let real_name = profile["real_name"].as_str()
+        .or_else(|| member["real_name"].as_str())
+        .unwrap_or_default();
+    let color = member["color"].as_str()
+        .or_else(|| profile["color"].as_str());
+
+    Ok(User{
+        id: id.to_string(),
+        name: name.to_string(),
+        real_name: real_name.to_string(),
+        display_name: profile["display_name"].as_str().unwrap_or_default().to_string(),
+        color: color.map(|color| color.to_string()),
+        is_bot: member["is_bot"].as_bool().unwrap_or(false),
+    })
+}
@@ -0,0 +1,47 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone};
+use serde::Deserialize;
+
+// "time_format" in the config
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Deserialize)]
+pub enum TimeFormat {
+    #[default]
+    #[serde(rename = "24h")]
+    TwentyFourHour,
+    #[serde(rename = "12h")]
+    TwelveHour,
+    #[serde(rename = "relative")]
+    Relative,
+}
+
+// Parse a Slack timestamp, "1512085950.000216", into local time
+pub fn parse_ts(ts: &str) -> Option<DateTime<Local>> {
+    let (seconds, micros) = ts.split_once('.').unwrap_or((ts, "0"));
+    let seconds = seconds.parse().ok()?;
+    let nanos = micros.parse::<u32>().unwrap_or(0).saturating_mul(1000);
+    Local.timestamp_opt(seconds, nanos).single()
+}
+
+pub fn format_time(time: &DateTime<Local>, format: TimeFormat, now: &DateTime<Local>) -> String {
+    match format {
+        TimeFormat::TwentyFourHour => time.format("%H:%M").to_string(),
+        TimeFormat::TwelveHour => time.format("%l:%M %p").to_string().trim_start().to_string(),
+        TimeFormat::Relative => {
+            let minutes = now.signed_duration_since(*time).num_minutes();
+            match minutes {
+                i64::MIN..=0 => "just now".to_string(),
+                1..=59 => format!("{}m ago", minutes),
+                60..=1439 => format!("{}h ago", minutes / 60),
+                _ => format!("{}d ago", minutes / 1440),
+            }
+        }
+    }
+}
+
+// "Tuesday, March 3", with the year added for dates in other years
+pub fn format_date(date: &NaiveDate, today: &NaiveDate) -> String {
+    if date.year() == today.year() {
+        date.format("%A, %B %-d").to_string()
+    } else {
+        date.format("%A, %B %-d, %Y").to_string()
+    }
+}
@@ -1,25 +1,33 @@
 use tui::{
-    backend::{CrosstermBackend, Backend},
+    backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Span, Spans},
     widgets::{
-        Block, BorderType, Borders, Cell, List, ListItem,
-        ListState, Paragraph, Row, Table, Tabs, Wrap,
+        Block, BorderType, Borders, Clear, List, ListItem,
+        ListState, Paragraph, Wrap,
     },
     Frame,
 };
 
-use crate::util;
+use chrono::{DateTime, Local};
+use std::ops::Range;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{emoji::{EmojiPicker, PickerMode}, mrkdwn, timestamp, util};
+use crate::keymap::{Action, Context};
 use crate::app::{
-    App, ActiveBlock,
+    App, ActiveBlock, MessagesScroll, Unread,
 };
+use crate::slack_interface::{channel_interface, messages_interface::Message};
 
 pub const MARGIN: u16 = 1;
 pub const MESSAGES_HEIGHT_PERCENTAGE: u16 = 90;
 pub const CHANNELS_WIDTH: u16 = 20;
+// Workspaces shown in the Teams pane before it scrolls
+pub const MAX_TEAMS_HEIGHT: u16 = 4;
 
-pub fn draw_ui<B: Backend>(frame: &mut Frame<B>, app: &App<'_>)
+pub fn draw_ui<B: Backend>(frame: &mut Frame<B>, app: &mut App)
 -> Result<(), Box<dyn std::error::Error>> {
     let size = frame.size();
     let root_chunk = Layout::default()
@@ -37,20 +45,54 @@ pub fn draw_ui<B: Backend>(frame: &mut Frame<B>, app: &App<'_>)
     // Render teams, channels and users
     draw_lists(frame, app, root_chunk[0]);
 
-    // Render messages and messages input
-    draw_conversation(frame, app, root_chunk[1]);
+    // Render messages and messages input, or why the workspace could not be loaded
+    match &app.auth_error {
+        Some(auth_error) => draw_auth_error(frame, app, auth_error, root_chunk[1]),
+        None => draw_conversation(frame, app, root_chunk[1]),
+    }
 
     Ok(())
 }
 
-pub fn draw_lists<B>(frame: &mut Frame<B>, app: &App<'_>, chunk: Rect)
+pub fn draw_auth_error<B>(frame: &mut Frame<B>, app: &App, auth_error: &str, chunk: Rect)
+where
+B: Backend{
+    let name = &app.workspaces[app.active_workspace].name;
+    let text = vec![
+        Spans::from(Span::styled(auth_error.to_owned(), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))),
+        Spans::default(),
+        Spans::from(format!("The token of {} was rejected, it may have been revoked or expired.", name)),
+        Spans::from(format!("Run `slacker-tui login {}` to sign in again, or `slacker-tui config check` to check every token.", name)),
+        Spans::default(),
+        Spans::from(Span::styled(
+            format!("Other workspaces can still be opened from Teams{}", get_key_hint(app, Context::Global, Action::Quit, "quit")),
+            Style::default().fg(Color::DarkGray))),
+    ];
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!("Could not sign in to {}", name))
+        .border_type(BorderType::Plain)
+        .border_style(Style::default().fg(Color::Red));
+
+    let paragraph = Paragraph::new(text)
+        .block(block)
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: false });
+
+    frame.render_widget(paragraph, chunk);
+}
+
+pub fn draw_lists<B>(frame: &mut Frame<B>, app: &App, chunk: Rect)
 where
     B: Backend{
+    // The teams pane fits the workspaces, with borders
+    let teams_height = (app.workspaces.len() as u16).clamp(1, MAX_TEAMS_HEIGHT) + 2;
     let channel_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
             [
-                Constraint::Percentage(10),     // Teams
+                Constraint::Length(teams_height),     // Teams
                 Constraint::Percentage(55),     // Channels
                 Constraint::Percentage(35),     // Users
             ]
@@ -63,16 +105,25 @@ where
     draw_users(frame, app, channel_chunks[2]);
 }
 
-pub fn draw_teams<B>(frame: &mut Frame<B>, app: &App<'_>, chunk: Rect)
+pub fn draw_teams<B>(frame: &mut Frame<B>, app: &App, chunk: Rect)
 where
 B: Backend{
     let title = "Teams";
 
-    // Temp teams
-    let team_list = vec!["Imagimob", "Other"];
-    // Get Teams
-    let items: Vec<_> = team_list.iter()
-        .map(|team| team.to_owned())
+    // Get workspaces with their unread messages, the shown one is marked and the ones
+    // that could not sign in are red
+    let items: Vec<_> = app.workspaces.iter().enumerate()
+        .map(|(index, workspace)| {
+            let (name, mut style) = if index == app.active_workspace {
+                (format!("▸ {}", workspace.name), Style::default().fg(Color::Cyan))
+            } else {
+                (format!("  {}", workspace.name), Style::default())
+            };
+            if app.has_auth_error(index) {
+                style = style.fg(Color::Red);
+            }
+            get_conversation_item(name, Some(&app.get_workspace_unread(index)), style)
+        })
         .collect();
 
     let highlight_state = (
@@ -80,19 +131,38 @@ B: Backend{
         app.hovered_block == ActiveBlock::Teams,
     );
 
-    draw_selectable_list(frame, app, chunk, title, &items,
-        highlight_state, &app.selected_team_index);
+    draw_selectable_list(frame, chunk, title, items,
+        highlight_state, app.selected_team_index);
 }
 
-pub fn draw_channels<B>(frame: &mut Frame<B>, app: &App<'_>, chunk: Rect)
+pub fn draw_channels<B>(frame: &mut Frame<B>, app: &App, chunk: Rect)
 where
 B: Backend{
-    let title = "Channels";
-
-    // Get channels
-    let items: Vec<_> = app.channel_list.iter()
-        .map(|item| item.name.to_owned())
-        .collect();
+    let title = get_title(app, "Channels", ActiveBlock::Channels);
+
+    // Get channels under a header for each section
+    let mut items = Vec::new();
+    let mut selected_index = None;
+    let mut section = None;
+    for (index, channel) in app.channel_list.iter().enumerate() {
+        if section != Some(channel.kind.get_section()) {
+            section = Some(channel.kind.get_section());
+            items.push(ListItem::new(Span::styled(
+                channel_interface::SECTIONS[channel.kind.get_section()],
+                Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD))));
+        }
+        if app.selected_channel_index == Some(index) {
+            selected_index = Some(items.len());
+        }
+
+        let name = format!("  {}", app.get_conversation_name(channel));
+        let style = if channel.is_archived {
+            Style::default().fg(Color::DarkGray)
+        } else {
+            Style::default()
+        };
+        items.push(get_conversation_item(name, app.unread.get(&channel.id), style));
+    }
 
     // let current_route = app.get_current_route();
     let highlight_state = (
@@ -100,18 +170,25 @@ B: Backend{
         app.hovered_block == ActiveBlock::Channels,
     );
 
-    draw_selectable_list(frame, app, chunk, title, &items,
-        highlight_state, &app.selected_channel_index);
+    draw_selectable_list(frame, chunk, &title, items, highlight_state, selected_index);
 }
 
-pub fn draw_users<B>(frame: &mut Frame<B>, app: &App<'_>, chunk: Rect)
+pub fn draw_users<B>(frame: &mut Frame<B>, app: &App, chunk: Rect)
 where
 B: Backend{
-    let title = "Users";
+    let title = get_title(app, "Users", ActiveBlock::Users);
 
-    // Get users
+    // Get users, marked with their presence once it is known and with the unread messages
+    // of their IM
     let items: Vec<_> = app.user_list.iter()
-        .map(|item| item.name.to_owned())
+        .map(|item| {
+            let name = match app.presence.get(&item.id).map(|p| p.as_str()) {
+                Some("active") => format!("● {}", item.get_display_name()),
+                Some(_) => format!("○ {}", item.get_display_name()),
+                None => item.get_display_name().to_owned(),
+            };
+            get_conversation_item(name, app.get_direct_message_unread(&item.id), Style::default())
+        })
         .collect();
 
     // let current_route = app.get_current_route();
@@ -120,13 +197,50 @@ B: Backend{
         app.hovered_block == ActiveBlock::Users,
     );
 
-    draw_selectable_list(frame, app, chunk, title, &items,
-        highlight_state, &app.selected_user_index);
+    draw_selectable_list(frame, chunk, &title, items,
+        highlight_state, app.selected_user_index);
+}
+
+// Unread conversations are bold with the number of unread messages, which is red when
+// the user is mentioned. Counts that stop at the first page end with a plus
+fn get_conversation_item(name: String, unread: Option<&Unread>, style: Style) -> ListItem<'static> {
+    let Some(unread) = unread.filter(|unread| unread.count > 0) else {
+        return ListItem::new(Span::styled(name, style));
+    };
+    let badge_style = if unread.mentions > 0 {
+        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::Gray)
+    };
+    ListItem::new(Spans::from(vec![
+        Span::styled(name, style.add_modifier(Modifier::BOLD)),
+        Span::styled(format!(" ({}{})", unread.count, if unread.capped { "+" } else { "" }), badge_style),
+    ]))
 }
 
-pub fn draw_conversation<B>(frame: &mut Frame<B>, app: &App<'_>, chunk: Rect)
+// Add a spinner to the title while `block` is waiting for the network
+fn get_title(app: &App, title: &str, block: ActiveBlock) -> String {
+    if app.is_loading(block) {
+        format!("{} {}", title, util::get_spinner(app.spinner_index))
+    } else {
+        title.to_string()
+    }
+}
+
+pub fn draw_conversation<B>(frame: &mut Frame<B>, app: &mut App, chunk: Rect)
 where
 B: Backend{
+    // An open thread is shown to the right of the messages and input
+    let mut chunk = chunk;
+    if app.thread_ts.is_some() {
+        let thread_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+            .split(chunk);
+        chunk = thread_chunks[0];
+        draw_thread(frame, app, thread_chunks[1]);
+    }
+
     let conversation_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
@@ -137,23 +251,180 @@ B: Backend{
 
     draw_conversation_messages(frame, app, conversation_chunks[0]);
     draw_conversation_input(frame, app, conversation_chunks[1]);
+
+    if let Some(picker) = &app.emoji_picker {
+        draw_emoji_picker(frame, picker, conversation_chunks[0]);
+    }
+    if app.pending_delete.is_some() {
+        draw_confirm(frame, "Delete this message? (y/n)", conversation_chunks[0]);
+    }
+
+    // Draw errors on top of the bottom of the messages pane
+    if let Some(error_message) = &app.error_message {
+        let title = format!("Error{}", get_key_hint(app, Context::Global, Action::Deselect, "dismiss"));
+        draw_error(frame, &title, error_message, conversation_chunks[0]);
+    }
 }
 
-pub fn draw_conversation_messages<B>(frame: &mut Frame<B>, app: &App<'_>, chunk: Rect)
+pub fn draw_conversation_messages<B>(frame: &mut Frame<B>, app: &mut App, chunk: Rect)
 where
 B: Backend{
-    let mut title = "No Conversation Selected";
+    let mut title = "No Conversation Selected".to_string();
     if let Some(conversation_name) = &app.active_conversation_name{
-        title = &conversation_name;
-    } 
+        title = conversation_name.to_owned();
+    }
+    // Followed by the topic of the channel
+    if let Some(channel) = app.channel_list.iter()
+        .find(|c| app.is_active_conversation(&c.id) && !c.topic.is_empty()) {
+        title = format!("{} | {}", title, channel.topic);
+    }
+    let title = get_title(app, &title, ActiveBlock::Messages);
+
+    // Inside the borders
+    let width = chunk.width.saturating_sub(2) as usize;
+    let height = chunk.height.saturating_sub(2) as usize;
+    let (items, message_lines) = get_message_spans(app, &app.messages_list,
+        app.selected_message_ts.as_deref(), true, width);
+
+    let top = get_messages_top(app, &message_lines, items.len(), height);
+    if app.messages_scroll == MessagesScroll::Bottom {
+        app.new_messages_below = 0;
+    }
 
-    // Get conversations
-    let items: Vec<_> = app.messages_list.iter().rev()
-        .map(|item|
-            Spans::from(vec![
+    let highlight_state = (
+        app.active_block == ActiveBlock::Messages,
+        app.hovered_block == ActiveBlock::Messages,
+    );
+
+    draw_paragraph(frame, chunk, &title, items, highlight_state, u16::try_from(top).unwrap_or(u16::MAX));
+
+    if app.new_messages_below > 0 && height > 0 {
+        let text = match app.new_messages_below {
+            1 => " 1 new message ↓ ".to_string(),
+            count => format!(" {} new messages ↓ ", count),
+        };
+        let indicator_width = (text.width() as u16).min(chunk.width.saturating_sub(2));
+        let indicator_chunk = Rect::new(
+            chunk.x + (chunk.width - indicator_width) / 2,
+            chunk.y + chunk.height - 2,
+            indicator_width, 1);
+        let indicator = Paragraph::new(Span::styled(text,
+            Style::default().fg(Color::Black).bg(Color::Cyan).add_modifier(Modifier::BOLD)));
+        frame.render_widget(indicator, indicator_chunk);
+    }
+}
+
+// Get the first line to show in the messages pane and remember it in app.messages_scroll.
+// The view stays where it was unless the selected message is out of view
+fn get_messages_top(app: &mut App, message_lines: &[(String, Range<usize>)],
+    line_count: usize, height: usize) -> usize {
+    let bottom = line_count.saturating_sub(height);
+    let get_lines = |ts: &str| message_lines.iter()
+        .find(|(message_ts, _)| message_ts == ts)
+        .map(|(_, lines)| lines.clone());
+
+    let mut top = match &app.messages_scroll {
+        MessagesScroll::Bottom => bottom,
+        MessagesScroll::Anchor { ts, offset } => get_lines(ts)
+            .map(|lines| lines.start + offset)
+            .unwrap_or(bottom),
+    }.min(bottom);
+
+    if let Some(lines) = app.selected_message_ts.as_deref().and_then(get_lines) {
+        if lines.end > top + height {
+            top = lines.end - height;
+        }
+        if lines.start < top {
+            top = lines.start;
+        }
+    }
+
+    app.messages_scroll = match message_lines.iter().find(|(_, lines)| lines.end > top) {
+        Some((ts, lines)) if top < bottom => MessagesScroll::Anchor {
+            ts: ts.clone(),
+            offset: top - lines.start,
+        },
+        _ => MessagesScroll::Bottom,
+    };
+    top
+}
+
+pub fn draw_thread<B>(frame: &mut Frame<B>, app: &mut App, chunk: Rect)
+where
+B: Backend{
+    let title = get_title(app, "Thread", ActiveBlock::Thread);
+
+    // Replies are counted on the parent in the messages pane already
+    let width = chunk.width.saturating_sub(2) as usize;
+    let (items, _) = get_message_spans(app, &app.thread_messages, None, false, width);
+
+    // thread_scroll counts wrapped lines, it stops where the last line is at the bottom
+    let height = chunk.height.saturating_sub(2) as usize;
+    let bottom = items.len().saturating_sub(height);
+    app.thread_scroll = app.thread_scroll.min(bottom.try_into().unwrap_or(u16::MAX));
+
+    let highlight_state = (
+        app.active_block == ActiveBlock::Thread,
+        app.hovered_block == ActiveBlock::Thread,
+    );
+
+    draw_paragraph(frame, chunk, &title, items, highlight_state, app.thread_scroll);
+}
+
+// Messages oldest first with date separators between days, wrapped to `width`. Consecutive
+// messages from the same author within the group window are shown without the time and name.
+// Also returns the lines of every message by timestamp, date separators included
+fn get_message_spans(app: &App, messages: &[Message], selected_ts: Option<&str>,
+    show_replies: bool, width: usize) -> (Vec<Spans<'static>>, Vec<(String, Range<usize>)>) {
+    let now = Local::now();
+    let user_id = app.identity.as_ref().map(|identity| identity.user_id.as_str());
+    let names = mrkdwn::Names {
+        users: &app.user_directory,
+        channels: &app.channel_list,
+        user_id,
+    };
+    let mut wrapped_items = Vec::new();
+    let mut message_lines = Vec::new();
+    let mut previous: Option<(&Message, DateTime<Local>)> = None;
+
+    for message in messages.iter().rev() {
+        let mut items = Vec::new();
+        let Some(time) = timestamp::parse_ts(&message.ts) else {
+            let start = wrapped_items.len();
+            wrapped_items.extend(util::wrap_spans(&Spans::from(message.text.clone()), width));
+            message_lines.push((message.ts.clone(), start..wrapped_items.len()));
+            continue;
+        };
+
+        let is_same_day = previous.is_some_and(|(_, previous_time)|
+            previous_time.date_naive() == time.date_naive());
+        if !is_same_day {
+            items.push(Spans::from(Span::styled(
+                format!("── {} ──", timestamp::format_date(&time.date_naive(), &now.date_naive())),
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+
+        let first_line = items.len();
+        let time_text = timestamp::format_time(&time, app.time_format, &now);
+        let is_grouped = is_same_day && previous.is_some_and(|(previous_message, previous_time)|
+            previous_message.username == message.username
+                && (time - previous_time).num_seconds() <= app.message_group_window.as_secs() as i64);
+
+        let mut lines = mrkdwn::render(&mrkdwn::parse(&message.text), &names).into_iter();
+        // Line the text up with the text of the first message in the group
+        let indent = " ".repeat(time_text.width() + 3);
+
+        if !is_grouped {
+            // The user's own messages are always green
+            let author_color = match user_id == Some(message.username.as_str()) {
+                true => Color::Green,
+                false => get_user_color(app, &message.username),
+            };
+            let mut header = vec![
                 Span::raw("["),
                 Span::styled(
-                    item.ts.clone(), // TODO: Format time
+                    time_text,
                     Style::default()
                         .fg(Color::Yellow)
                         .add_modifier(Modifier::BOLD),
@@ -162,33 +433,95 @@ B: Backend{
                 Span::raw(" "),
                 Span::raw("<"),
                 Span::styled(
-                    item.username.clone(),
+                    app.user_directory.get_display_name(&message.username).to_owned(),
                     Style::default()
-                        .fg(Color::Cyan)
+                        .fg(author_color)
                         .add_modifier(Modifier::BOLD),
                 ),
                 Span::raw(">"),
                 Span::raw(" "),
-                Span::styled(
-                    item.text.clone(),
-                    Style::default(),
-                ),
-            ])
-        ).collect();
-
-    let highlight_state = (
-        app.active_block == ActiveBlock::Messages,
-        app.hovered_block == ActiveBlock::Messages,
-    );
+            ];
+            header.extend(lines.next().map(|line| line.0).unwrap_or_default());
+            items.push(Spans::from(header));
+        }
+
+        for line in lines {
+            let mut spans = vec![Span::raw(indent.clone())];
+            spans.extend(line.0);
+            items.push(Spans::from(spans));
+        }
+
+        if message.is_edited {
+            if let Some(last_line) = items.last_mut() {
+                last_line.0.push(Span::styled(" (edited)", Style::default().fg(Color::DarkGray)));
+            }
+        }
+
+        if !message.reactions.is_empty() {
+            let mut spans = vec![Span::raw(indent.clone())];
+            for reaction in &message.reactions {
+                spans.push(Span::styled(format!(":{}: {}", reaction.name, reaction.count),
+                    Style::default().fg(Color::Black).bg(Color::Gray)));
+                spans.push(Span::raw(" "));
+            }
+            items.push(Spans::from(spans));
+        }
+
+        if show_replies && message.reply_count > 0 {
+            let replies = match message.reply_count {
+                1 => "1 reply".to_string(),
+                count => format!("{} replies", count),
+            };
+            items.push(Spans::from(vec![
+                Span::raw(indent.clone()),
+                Span::styled(replies, Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            ]));
+        }
+
+        if selected_ts == Some(message.ts.as_str()) {
+            let highlight = Style::default().bg(Color::DarkGray);
+            for line in &mut items[first_line..] {
+                for span in &mut line.0 {
+                    span.style = span.style.patch(highlight);
+                }
+            }
+        }
+
+        let start = wrapped_items.len();
+        for line in &items {
+            wrapped_items.extend(util::wrap_spans(line, width));
+        }
+        message_lines.push((message.ts.clone(), start..wrapped_items.len()));
+
+        previous = Some((message, time));
+    }
+    (wrapped_items, message_lines)
+}
 
-    draw_paragraph(frame, app, chunk, title, items, highlight_state);
+fn get_user_color(app: &App, user_id: &str) -> Color {
+    app.user_directory.get(user_id)
+        .and_then(|user| user.color.as_deref())
+        .and_then(util::parse_color)
+        .unwrap_or(Color::Cyan)
 }
 
-pub fn draw_conversation_input<B>(frame: &mut Frame<B>, app: &App<'_>, chunk: Rect)
+pub fn draw_conversation_input<B>(frame: &mut Frame<B>, app: &App, chunk: Rect)
 where
 B: Backend{
-    let title = "Input";
-    let text_input: String = app.input.iter().collect();
+    let mut title = match (app.reply_in_thread, app.reply_broadcast) {
+        _ if app.editing_ts.is_some() => {
+            format!("Edit message{}", get_key_hint(app, Context::Input, Action::Deselect, "cancel"))
+        }
+        (false, _) => "Input".to_string(),
+        (true, false) => "Reply in thread".to_string(),
+        (true, true) => "Reply in thread and channel".to_string(),
+    };
+    let typing_users = app.get_typing_users();
+    if !typing_users.is_empty() {
+        title = format!("{} - {} typing…", title, typing_users.join(", "));
+    }
+    let title = get_title(app, &title, ActiveBlock::Input);
+    let text_input: String = app.input.iter().map(|c| util::get_input_symbol(*c)).collect();
 
     let items: Vec<Spans> = vec![
         Spans::from(vec![
@@ -201,11 +534,114 @@ B: Backend{
         app.hovered_block == ActiveBlock::Input,
     );
 
-    draw_paragraph(frame, app, chunk, title, items, highlight_state);
+    draw_paragraph(frame, chunk, &title, items, highlight_state, 0);
+}
+
+// Draw the emoji picker in the middle of `chunk`, the search on top of the matching names
+pub fn draw_emoji_picker<B>(frame: &mut Frame<B>, picker: &EmojiPicker, chunk: Rect)
+where
+B: Backend{
+    let width = 40.min(chunk.width);
+    let height = 14.min(chunk.height);
+    let picker_chunk = Rect::new(
+        chunk.x + (chunk.width - width) / 2,
+        chunk.y + (chunk.height - height) / 2,
+        width, height);
+
+    let title = match picker.mode {
+        PickerMode::Add => "Add reaction",
+        PickerMode::Remove => "Remove reaction",
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_type(BorderType::Plain)
+        .style(Style::default().fg(Color::White))
+        .border_style(util::get_color((true, true)));
+
+    let inner = block.inner(picker_chunk);
+    frame.render_widget(Clear, picker_chunk);
+    frame.render_widget(block, picker_chunk);
+    if inner.height < 2 {
+        return;
+    }
+
+    let search = Paragraph::new(Spans::from(vec![
+        Span::styled("Search: ", Style::default().fg(Color::DarkGray)),
+        Span::raw(picker.query.clone()),
+    ]));
+    frame.render_widget(search, Rect::new(inner.x, inner.y, inner.width, 1));
+
+    let items: Vec<ListItem> = picker.get_matches().iter()
+        .map(|name| ListItem::new(Span::raw(format!(":{}:", name))))
+        .collect();
+    let list = List::new(items)
+        .highlight_style(Style::default()
+            .bg(Color::Yellow)
+            .fg(Color::Black)
+            .add_modifier(Modifier::BOLD));
+    let mut state = ListState::default();
+    state.select(Some(picker.selected));
+    frame.render_stateful_widget(list,
+        Rect::new(inner.x, inner.y + 1, inner.width, inner.height - 1), &mut state);
+}
+
+// Draw a question in the middle of `chunk`
+pub fn draw_confirm<B>(frame: &mut Frame<B>, question: &str, chunk: Rect)
+where
+B: Backend{
+    let width = (question.width() as u16 + 4).min(chunk.width);
+    let height = 3.min(chunk.height);
+    let confirm_chunk = Rect::new(
+        chunk.x + (chunk.width - width) / 2,
+        chunk.y + (chunk.height - height) / 2,
+        width, height);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Confirm")
+        .border_type(BorderType::Plain)
+        .style(Style::default().fg(Color::Yellow));
+
+    let paragraph = Paragraph::new(Spans::from(vec![Span::raw(question.to_owned())]))
+        .block(block)
+        .alignment(Alignment::Center);
+
+    frame.render_widget(Clear, confirm_chunk);
+    frame.render_widget(paragraph, confirm_chunk);
+}
+
+// " (key to do something)" for the key bound to an action, empty if it has none
+fn get_key_hint(app: &App, context: Context, action: Action, text: &str) -> String {
+    match app.keymap.get_key_name(context, action) {
+        Some(key) => format!(" ({} to {})", key, text),
+        None => String::new(),
+    }
+}
+
+pub fn draw_error<B>(frame: &mut Frame<B>, title: &str, error_message: &str, chunk: Rect)
+where
+B: Backend{
+    let height = 3.min(chunk.height);
+    let error_chunk = Rect::new(chunk.x, chunk.y + chunk.height - height, chunk.width, height);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_type(BorderType::Plain)
+        .style(Style::default().fg(Color::Red));
+
+    let paragraph = Paragraph::new(Spans::from(vec![Span::raw(error_message.to_owned())]))
+        .block(block)
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: true });
+
+    frame.render_widget(Clear, error_chunk);
+    frame.render_widget(paragraph, error_chunk);
 }
 
-pub fn draw_paragraph<B>(frame: &mut Frame<B>, app: &App<'_>, chunk: Rect,
-    title: &str, items: Vec<Spans>, highlight_state: (bool, bool))
+pub fn draw_paragraph<B>(frame: &mut Frame<B>, chunk: Rect,
+    title: &str, items: Vec<Spans>, highlight_state: (bool, bool), scroll: u16)
 where
 B: Backend{
     let block = Block::default()
@@ -218,23 +654,18 @@ B: Backend{
     let paragraph = Paragraph::new(items)
         .block(block)
         .alignment(Alignment::Left)
-        .wrap(Wrap { trim: true });
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0));
 
     frame.render_widget(paragraph, chunk);
 }
 
-pub fn draw_selectable_list<B, S>(frame: &mut Frame<B>, app: &App<'_>, chunk: Rect,
-    title: &str, items: &[S], highlight_state: (bool, bool), selected_index: &Option<usize>)
+pub fn draw_selectable_list<B>(frame: &mut Frame<B>, chunk: Rect, title: &str,
+    list_items: Vec<ListItem>, highlight_state: (bool, bool), selected_index: Option<usize>)
 where
-B: Backend,
-S: std::convert::AsRef<str>{
+B: Backend{
     let mut state = ListState::default();
-    state.select(*selected_index);
-
-    let list_items: Vec<ListItem> = items
-        .iter()
-        .map(|i| ListItem::new(Span::raw(i.as_ref())))
-        .collect();
+    state.select(selected_index);
 
     let block = Block::default()
         .borders(Borders::ALL)
@@ -252,4 +683,70 @@ S: std::convert::AsRef<str>{
     );
 
     frame.render_stateful_widget(list, chunk, &mut state);
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::tests::create_app;
+    use crate::slack_interface::messages_interface;
+    use serde_json::json;
+    use tui::{backend::TestBackend, Terminal};
+
+    // Three messages of 3, 3 and 4 lines
+    fn get_message_lines() -> Vec<(String, Range<usize>)> {
+        vec![("1".to_string(), 0..3), ("2".to_string(), 3..6), ("3".to_string(), 6..10)]
+    }
+
+    #[test]
+    fn messages_stop_at_the_last_line() {
+        let mut app = create_app("messages-top");
+        let message_lines = get_message_lines();
+
+        app.messages_scroll = MessagesScroll::Bottom;
+        assert_eq!(get_messages_top(&mut app, &message_lines, 10, 4), 6);
+
+        app.messages_scroll = MessagesScroll::Anchor { ts: "1".to_string(), offset: 1 };
+        assert_eq!(get_messages_top(&mut app, &message_lines, 10, 4), 1);
+        assert_eq!(app.messages_scroll, MessagesScroll::Anchor { ts: "1".to_string(), offset: 1 });
+
+        // Scrolling past the end shows the last line at the bottom and follows new messages
+        app.messages_scroll = MessagesScroll::Anchor { ts: "3".to_string(), offset: 3 };
+        assert_eq!(get_messages_top(&mut app, &message_lines, 10, 4), 6);
+        assert_eq!(app.messages_scroll, MessagesScroll::Bottom);
+    }
+
+    #[test]
+    fn messages_scroll_to_the_selected_message() {
+        let mut app = create_app("messages-selected");
+        let message_lines = get_message_lines();
+
+        app.messages_scroll = MessagesScroll::Anchor { ts: "1".to_string(), offset: 0 };
+        app.selected_message_ts = Some("2".to_string());
+        assert_eq!(get_messages_top(&mut app, &message_lines, 10, 4), 2);
+        assert_eq!(app.messages_scroll, MessagesScroll::Anchor { ts: "1".to_string(), offset: 2 });
+
+        app.selected_message_ts = Some("1".to_string());
+        app.messages_scroll = MessagesScroll::Bottom;
+        assert_eq!(get_messages_top(&mut app, &message_lines, 10, 4), 0);
+    }
+
+    #[test]
+    fn thread_stops_at_the_last_wrapped_line() {
+        let mut app = create_app("thread-scroll");
+        app.thread_messages = (0..5).map(|i| messages_interface::parse_message(&json!({
+            "type": "message",
+            "user": "U061F7AUR",
+            "text": "Back off man, I'm a scientist. ".repeat(4),
+            "ts": format!("1512104434.00049{}", i),
+        })).expect("Parse message expect")).collect();
+        app.thread_scroll = u16::MAX;
+
+        let mut terminal = Terminal::new(TestBackend::new(30, 12)).expect("Test terminal expect");
+        terminal.draw(|frame| draw_thread(frame, &mut app, frame.size())).expect("Draw thread expect");
+
+        // The lines of the messages wrapped inside the borders, less the ones that fit
+        let (items, _) = get_message_spans(&app, &app.thread_messages, None, false, 28);
+        assert!(items.len() > 10);
+        assert_eq!(app.thread_scroll as usize, items.len() - 10);
+    }
+}
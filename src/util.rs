@@ -1,7 +1,14 @@
 use tui::{
-    style::{Color, Style}
+    style::{Color, Style},
+    text::{Span, Spans},
 };
-use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use unicode_width::UnicodeWidthChar;
+
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+pub fn get_spinner(index: usize) -> char {
+    SPINNER_FRAMES[index % SPINNER_FRAMES.len()]
+}
 
 pub fn get_color((is_active, is_hovered): (bool, bool)) -> Style {
     match (is_active, is_hovered) {
@@ -11,9 +18,135 @@ pub fn get_color((is_active, is_hovered): (bool, bool)) -> Style {
     }
 }
 
+// Parse a Slack color like "9f69e7"
+pub fn parse_color(hex: &str) -> Option<Color> {
+    let hex = hex.trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?))
+}
+
+// Line breaks in the input are shown as this
+pub const NEWLINE_SYMBOL: char = '↵';
+
+// Control characters like line breaks have no width
 pub fn calculate_character_width(character: char) -> u16 {
     UnicodeWidthChar::width(character)
-        .unwrap()
+        .unwrap_or(0)
         .try_into()
-        .unwrap()
-}
\ No newline at end of file
+        .unwrap_or(0)
+}
+
+// How a character of the input is shown, line breaks get a symbol so the input stays on one line
+pub fn get_input_symbol(character: char) -> char {
+    match character {
+        '\n' => NEWLINE_SYMBOL,
+        character => character,
+    }
+}
+
+// Width of a character of the input as it is shown
+pub fn calculate_input_width(character: char) -> u16 {
+    calculate_character_width(get_input_symbol(character))
+}
+
+// Wrap a line into lines of at most `width` columns, breaking between words where possible.
+// Lines are wrapped here instead of by the Paragraph so their number is known for scrolling
+pub fn wrap_spans(line: &Spans<'_>, width: usize) -> Vec<Spans<'static>> {
+    let width = width.max(1);
+    let characters: Vec<(char, Style)> = line.0.iter()
+        .flat_map(|span| span.content.chars().map(move |c| (c, span.style)))
+        .collect();
+
+    let mut lines: Vec<Vec<(char, Style)>> = vec![Vec::new()];
+    let mut line_width = 0;
+    let mut start = 0;
+    while start < characters.len() {
+        // Next word or run of spaces
+        let is_space = characters[start].0 == ' ';
+        let end = (start..characters.len())
+            .find(|&i| (characters[i].0 == ' ') != is_space)
+            .unwrap_or(characters.len());
+        let word = &characters[start..end];
+        let word_width: usize = word.iter().map(|(c, _)| c.width().unwrap_or(0)).sum();
+        start = end;
+
+        if line_width + word_width <= width {
+            lines.last_mut().expect("Wrapped line expect").extend_from_slice(word);
+            line_width += word_width;
+            continue;
+        }
+        // Spaces at the end of a line are dropped
+        if is_space {
+            lines.push(Vec::new());
+            line_width = 0;
+            continue;
+        }
+        if word_width <= width && line_width > 0 {
+            lines.push(word.to_vec());
+            line_width = word_width;
+            continue;
+        }
+
+        // Words longer than a line are split
+        for &(c, style) in word {
+            let char_width = c.width().unwrap_or(0);
+            if line_width + char_width > width && line_width > 0 {
+                lines.push(Vec::new());
+                line_width = 0;
+            }
+            lines.last_mut().expect("Wrapped line expect").push((c, style));
+            line_width += char_width;
+        }
+    }
+
+    lines.into_iter().map(|characters| {
+        // Join characters with the same style back into spans
+        let mut spans: Vec<Span<'static>> = Vec::new();
+        for (c, style) in characters {
+            match spans.last_mut() {
+                Some(span) if span.style == style => span.content.to_mut().push(c),
+                _ => spans.push(Span::styled(c.to_string(), style)),
+            }
+        }
+        Spans::from(spans)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_lines(text: &str, width: usize) -> Vec<String> {
+        wrap_spans(&Spans::from(text.to_string()), width).iter()
+            .map(|line| line.0.iter().map(|span| span.content.as_ref()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn wraps_between_words() {
+        // A space that still fits stays at the end of the line
+        assert_eq!(get_lines("who you gonna call", 10), vec!["who you ", "gonna call"]);
+        assert_eq!(get_lines("who you gonna call", 18), vec!["who you gonna call"]);
+        assert_eq!(get_lines("", 10), vec![""]);
+    }
+
+    #[test]
+    fn splits_long_words_and_counts_wide_characters() {
+        assert_eq!(get_lines("ectoplasmic residue", 6), vec!["ectopl", "asmic ", "residu", "e"]);
+        // Every character takes two columns
+        assert_eq!(get_lines("幽霊幽霊幽", 4), vec!["幽霊", "幽霊", "幽"]);
+    }
+
+    #[test]
+    fn keeps_styles_across_lines() {
+        let bold = Style::default().fg(Color::Red);
+        let line = Spans::from(vec![Span::raw("slimer "), Span::styled("ate everything", bold)]);
+        let lines = wrap_spans(&line, 10);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].0[0].content, "everything");
+        assert_eq!(lines[1].0[0].style, bold);
+    }
+}
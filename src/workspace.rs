@@ -0,0 +1,125 @@
+use crossterm::event::KeyEvent;
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::time::Instant;
+
+use crate::InputEvent;
+use crate::config::WorkspaceConfig;
+use crate::app::{ActiveBlock, MessagesScroll, Unread};
+use crate::network::{self, ApiRequest};
+use crate::slack_interface::{
+    auth_interface::Identity,
+    channel_interface::Channel,
+    messages_interface::Message,
+    rate_limiter::Priority,
+    user_interface::{User, UserDirectory},
+};
+
+pub struct Workspace {
+    // From the config until team.info has answered
+    pub name: String,
+    pub team_id: Option<String>,
+    // The state of the workspace while another one is shown, None for the shown workspace
+    pub state: Option<WorkspaceState>,
+}
+
+// Everything in App that belongs to one workspace. It is swapped with the fields of App
+// when switching workspace, so the rest of App only has to know about one workspace
+pub struct WorkspaceState {
+    pub oauth_token: String,
+    pub network: mpsc::Sender<ApiRequest>,
+    pub background_network: mpsc::Sender<ApiRequest>,
+    pub poll_network: mpsc::Sender<ApiRequest>,
+    // Token of the real-time connection, taken when auth.test accepts the token and the
+    // connection is started
+    pub realtime_token: Option<String>,
+    pub loading: HashMap<ActiveBlock, usize>,
+    pub realtime_connected: bool,
+    pub typing_users: HashMap<(String, String), Instant>,
+    pub presence: HashMap<String, String>,
+    pub last_poll: Instant,
+    pub last_unread_poll: Instant,
+    pub is_polling: bool,
+    pub pending_unread: usize,
+    pub unread_poll_offset: usize,
+    pub last_seen: HashMap<String, String>,
+    pub unread: HashMap<String, Unread>,
+    pub channel_list: Vec<Channel>,
+    pub user_list: Vec<User>,
+    pub user_directory: UserDirectory,
+    pub selected_channel_index: Option<usize>,
+    pub selected_user_index: Option<usize>,
+    pub messages_list: Vec<Message>,
+    pub messages_cursor: Option<String>,
+    pub selected_message_ts: Option<String>,
+    pub messages_scroll: MessagesScroll,
+    pub new_messages_below: usize,
+    pub thread_ts: Option<String>,
+    pub thread_messages: Vec<Message>,
+    pub thread_scroll: u16,
+    pub reply_in_thread: bool,
+    pub reply_broadcast: bool,
+    pub custom_emoji: Vec<String>,
+    pub is_emoji_requested: bool,
+    pub active_conversation_id: Option<String>,
+    pub active_conversation_name: Option<String>,
+    pub identity: Option<Identity>,
+    pub auth_error: Option<String>,
+}
+
+impl WorkspaceState {
+    // Start the network workers of a workspace. Their events are tagged with `index`, the
+    // position of the workspace in the config
+    pub fn new(config: &WorkspaceConfig, index: usize, page_limit: u64,
+        events: mpsc::Sender<InputEvent<KeyEvent>>) -> Self {
+        let realtime_token = config.app_token.as_ref().unwrap_or(&config.oauth_token);
+
+        // Unread checks and read states give way to the requests the user is waiting for
+        let background_network = network::start_worker(
+            config.oauth_token.to_string(), page_limit, index, Priority::Background, events.clone());
+        let poll_network = network::start_worker(
+            config.oauth_token.to_string(), page_limit, index, Priority::Interactive, events.clone());
+        let network = network::start_worker(
+            config.oauth_token.to_string(), page_limit, index, Priority::Interactive, events);
+
+        WorkspaceState {
+            oauth_token: config.oauth_token.to_string(),
+            network,
+            background_network,
+            poll_network,
+            realtime_token: Some(realtime_token.to_string()),
+            loading: HashMap::new(),
+            realtime_connected: false,
+            typing_users: HashMap::new(),
+            presence: HashMap::new(),
+            last_poll: Instant::now(),
+            last_unread_poll: Instant::now(),
+            is_polling: false,
+            pending_unread: 0,
+            unread_poll_offset: 0,
+            last_seen: HashMap::new(),
+            unread: HashMap::new(),
+            channel_list: Vec::new(),
+            user_list: Vec::new(),
+            user_directory: UserDirectory::default(),
+            selected_channel_index: None,
+            selected_user_index: None,
+            messages_list: Vec::new(),
+            messages_cursor: None,
+            selected_message_ts: None,
+            messages_scroll: MessagesScroll::Bottom,
+            new_messages_below: 0,
+            thread_ts: None,
+            thread_messages: Vec::new(),
+            thread_scroll: 0,
+            reply_in_thread: false,
+            reply_broadcast: false,
+            custom_emoji: Vec::new(),
+            is_emoji_requested: false,
+            active_conversation_id: None,
+            active_conversation_name: None,
+            identity: None,
+            auth_error: None,
+        }
+    }
+}